@@ -0,0 +1,111 @@
+use std::fmt::Display;
+
+use genai::chat::ChatMessage;
+use logi::embed::Embedder;
+use logi::llm::{AiService, LLMService, DEFAULT_MODEL};
+use logi::setup_bsky_sess;
+use logi::vdb::{CollectionTuning, MemoryStore};
+
+use crate::{embedding_dim_from_env, memory_enabled_from_env, qdrant_db_from_env};
+
+/// Checks every external dependency the bot needs and prints a pass/fail line for
+/// each. Returns whether all of them passed.
+pub async fn run() -> bool {
+    println!("Running self-test...");
+    let embedding_dim = embedding_dim_from_env();
+
+    let mut results = vec![report("Bluesky login", check_login().await)];
+    // Without memory, neither Qdrant nor the embedder is used
+    if memory_enabled_from_env() {
+        results.push(report("Qdrant", check_qdrant(embedding_dim).await));
+        results.push(report("Embedder", check_embedder(embedding_dim)));
+    } else {
+        println!("[SKIP] Qdrant, Embedder: MEMORY_ENABLED is false");
+    }
+    results.push(report("LLM", check_llm().await));
+
+    let failed = results.iter().filter(|passed| !**passed).count();
+    if failed == 0 {
+        println!("All checks passed.");
+    } else {
+        println!("{} of {} checks failed.", failed, results.len());
+    }
+    failed == 0
+}
+
+fn report(name: &str, result: anyhow::Result<impl Display>) -> bool {
+    match result {
+        Ok(detail) => {
+            println!("[PASS] {}: {}", name, detail);
+            true
+        }
+        Err(e) => {
+            println!("[FAIL] {}: {:#}", name, e);
+            false
+        }
+    }
+}
+
+async fn check_login() -> anyhow::Result<String> {
+    let (_, did) = setup_bsky_sess().await?;
+    Ok(format!("logged in as {}", did.as_str()))
+}
+
+async fn check_qdrant(embedding_dim: usize) -> anyhow::Result<String> {
+    let url = std::env::var("QDRANT_URL").map_err(|_| anyhow::anyhow!("QDRANT_URL not set"))?;
+    let collection = qdrant_db_from_env();
+    let vdb = MemoryStore::new(
+        &url,
+        &collection,
+        embedding_dim,
+        CollectionTuning::from_env(),
+    )
+    .await?;
+    match vdb.vector_dim().await? {
+        Some(dim) if dim == embedding_dim => Ok(format!(
+            "collection '{}' has {} dimensions",
+            collection, dim
+        )),
+        Some(dim) => Err(anyhow::anyhow!(
+            "collection '{}' has {} dimensions, but EMBEDDING_DIM is {}",
+            collection,
+            dim,
+            embedding_dim
+        )),
+        None => Err(anyhow::anyhow!(
+            "collection '{}' has no single unnamed vector",
+            collection
+        )),
+    }
+}
+
+fn check_embedder(embedding_dim: usize) -> anyhow::Result<String> {
+    let emb = Embedder::new()?;
+    let vector = emb
+        .embed(vec!["self-test".to_string()])?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("embedder returned no vector"))?;
+    if vector.len() != embedding_dim {
+        return Err(anyhow::anyhow!(
+            "embedder produces {} dimensions, but EMBEDDING_DIM is {}",
+            vector.len(),
+            embedding_dim
+        ));
+    }
+    Ok(format!("produces {} dimensions", vector.len()))
+}
+
+async fn check_llm() -> anyhow::Result<String> {
+    let llm = LLMService::new(None, vec![], DEFAULT_MODEL)?;
+    let response = llm
+        .generate_response(&[ChatMessage::user("Reply with just the word OK.")], None)
+        .await?;
+    if response.trim().is_empty() {
+        return Err(anyhow::anyhow!(
+            "{} returned an empty response",
+            llm.model()
+        ));
+    }
+    Ok(format!("{} responded", llm.model()))
+}
@@ -3,15 +3,29 @@ use colored::*;
 use futures_util::StreamExt;
 use genai::chat::ChatMessage;
 use genai::chat::ToolResponse;
-use logi::llm::{AiService, LLMService};
+use logi::ingestors::post_listener::{conversation_id, tool_trace_id};
+use logi::llm::{AiService, LLMService, ReasoningEffort};
+use logi::sanitize::sanitize_output;
 use logi::tools::calc::MathTool;
+use logi::tools::dictionary::DictionaryTool;
+use logi::tools::github::GithubTool;
+use logi::tools::ocr::OcrTool;
+use logi::tools::price::PriceTool;
 use logi::tools::search::DDGSearchTool;
+use logi::tools::summarize::SummarizeTool;
+use logi::tools::translate::TranslateTool;
 use logi::tools::website::WebsiteTool;
+use logi::tools::{AiTool, ToolContext, ToolError, TOOL_TIMEOUT};
+use logi::vdb::Distance;
 use regex::Regex;
 use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 use termimad::MadSkin;
 
+use crate::memory::{CliMemory, QdrantEnv};
+use crate::settings::{CliSettings, SETTINGS_PATH};
+
 /// Replace Markdown links with OSC 8 hyperlinks for supported terminals.
 fn add_osc8_hyperlinks(input: &str) -> String {
     let re = Regex::new(r"\[([^\]]+)\]\(([^)]+)\)").unwrap();
@@ -28,15 +42,216 @@ fn clear_line() {
     print!("\r\x1b[2K");
 }
 
-const TOOL_CALL_TIMES: usize = 3; // Maximum number of repeated tool calls allowed
+const DEFAULT_MODEL: &str = "DeepSeek-R1-0528";
+const PROMPT_PATH: &str = "prompt_cli.txt";
+const TOOL_DESCRIPTION_WIDTH: usize = 72; // Characters of each tool description shown in tool lists
+const TOOL_RESULT_PREVIEW_CHARS: usize = 600; // Characters of a tool result shown outside /verbose
+const SEARCH_SNIPPET_WIDTH: usize = 100; // Characters of each search snippet shown outside /verbose
+
+/// Options for the CLI session, parsed from command-line flags.
+#[derive(Debug, Default, Clone)]
+pub struct CliOptions {
+    /// Print the model's reasoning before each answer (`--show-reasoning`).
+    pub show_reasoning: bool,
+    /// Fetch whole responses instead of streaming them (`--no-stream`).
+    pub no_stream: bool,
+    /// Delete and recreate the memory collection, then exit (`--reset-memory`).
+    pub reset_memory: bool,
+    /// Write the transcript of a bot conversation, given by its root post URI or
+    /// conversation ID, then exit (`--export <conversation>`).
+    pub export: Option<String>,
+    /// Where `--export` writes to (`--export-file <path>`). Defaults to
+    /// `<conversation id>.txt`.
+    pub export_file: Option<PathBuf>,
+    /// System prompt file to use instead of `prompt_cli.txt` (`--prompt-file <path>`).
+    pub prompt_file: Option<PathBuf>,
+    /// Model to start with, overriding `MODEL` (`--model <name>`).
+    pub model: Option<String>,
+}
+
+impl CliOptions {
+    /// Parses options from the process arguments, ignoring unknown flags.
+    pub fn from_args() -> Self {
+        let mut options = Self::default();
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--show-reasoning" => options.show_reasoning = true,
+                "--no-stream" => options.no_stream = true,
+                "--reset-memory" => options.reset_memory = true,
+                "--export" => match args.next() {
+                    Some(conversation) => options.export = Some(conversation),
+                    None => eprintln!("--export needs a conversation URI or ID"),
+                },
+                "--export-file" => match args.next() {
+                    Some(path) => options.export_file = Some(PathBuf::from(path)),
+                    None => eprintln!("--export-file needs a path"),
+                },
+                "--prompt-file" => match args.next() {
+                    Some(path) => options.prompt_file = Some(PathBuf::from(path)),
+                    None => eprintln!("--prompt-file needs a path"),
+                },
+                "--model" => match args.next() {
+                    Some(model) => options.model = Some(model),
+                    None => eprintln!("--model needs a model name"),
+                },
+                other => eprintln!("Ignoring unknown argument: {}", other),
+            }
+        }
+        options
+    }
+}
+
+/// Wipes the memory collection named by `QDRANT_DB` after the user types its name to confirm.
+///
+/// The collection is recreated with `EMBEDDING_DIM` dimensions (default 1536) and cosine distance.
+pub async fn reset_memory() -> Result<()> {
+    let qdrant = QdrantEnv::from_env()?;
+    let (url, collection, embedding_dim) = (&qdrant.url, &qdrant.collection, qdrant.embedding_dim);
+
+    println!(
+        "{}",
+        format!(
+            "This permanently deletes every memory in collection '{}' at {}.",
+            collection, url
+        )
+        .red()
+        .bold()
+    );
+    print!("Type the collection name to confirm: ");
+    io::stdout().flush()?;
+    let mut confirmation = String::new();
+    io::stdin().read_line(&mut confirmation)?;
+    if confirmation.trim() != collection.as_str() {
+        println!("Confirmation did not match, leaving memory untouched.");
+        return Ok(());
+    }
+
+    let store = qdrant.connect().await?;
+    store.recreate(embedding_dim, Distance::Cosine).await?;
+    println!(
+        "{}",
+        format!(
+            "Recreated '{}' with {} dimensions.",
+            collection, embedding_dim
+        )
+        .magenta()
+    );
+    Ok(())
+}
+
+/// Writes the stored exchanges of a bot conversation to a transcript file.
+///
+/// `conversation` is the AT-URI of the thread's root post, or the conversation ID
+/// derived from it.
+pub async fn export_conversation(conversation: &str, file: Option<&Path>) -> Result<()> {
+    let conversation = conversation.trim();
+    let id = if conversation.starts_with("at://") {
+        conversation_id(conversation)
+    } else {
+        conversation.to_string()
+    };
+
+    let store = QdrantEnv::from_env()?.connect().await?;
+    let Some(transcript) = store.transcript(&id).await? else {
+        println!("Nothing is stored for conversation {}.", id);
+        return Ok(());
+    };
+
+    let path = file
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from(format!("{}.txt", id)));
+    std::fs::write(&path, transcript)?;
+    println!(
+        "{}",
+        format!("Wrote conversation {} to {}.", id, path.display()).magenta()
+    );
+    Ok(())
+}
+
+/// Prints accumulated reasoning in a dim color under a "Reasoning:" header.
+fn print_reasoning(reasoning: &str) {
+    if reasoning.trim().is_empty() {
+        return;
+    }
+    println!("{}", "Reasoning:".dimmed().bold());
+    println!("{}", reasoning.trim().dimmed());
+    println!();
+}
+
+/// Splits a `<think>...</think>` block off the front of a response, returning the
+/// reasoning (if any) and the answer.
+fn split_reasoning(response: &str) -> (Option<&str>, &str) {
+    match response.rsplit_once("</think>") {
+        Some((reasoning, answer)) => (
+            Some(reasoning.trim_start().trim_start_matches("<think>")),
+            answer.trim(),
+        ),
+        None => (None, response.trim()),
+    }
+}
+
+/// Prints the assistant's response, streaming it unless `no_stream` is set.
+/// `searched` are retrieved memories, passed to the model as `searched_messages`.
+async fn print_assistant_response(
+    llm_service: &LLMService,
+    messages: &[ChatMessage],
+    searched: Option<&[ChatMessage]>,
+    show_reasoning: bool,
+    no_stream: bool,
+) -> String {
+    if no_stream {
+        print_assistant_response_full(llm_service, messages, searched, show_reasoning).await
+    } else {
+        print_assistant_response_stream(llm_service, messages, searched, show_reasoning).await
+    }
+}
+
+/// Fetches the whole response at once and prints it, returning the answer without reasoning.
+/// This avoids the cursor tricks used while streaming, so it behaves in non-TTY contexts.
+async fn print_assistant_response_full(
+    llm_service: &LLMService,
+    messages: &[ChatMessage],
+    searched: Option<&[ChatMessage]>,
+    show_reasoning: bool,
+) -> String {
+    match llm_service.generate_response(messages, searched).await {
+        Ok(response) => {
+            let (reasoning, answer) = split_reasoning(&response);
+            if show_reasoning {
+                if let Some(reasoning) = reasoning {
+                    print_reasoning(reasoning);
+                }
+            }
+            println!("{}", "Assistant:".green().bold());
+            let with_links = add_osc8_hyperlinks(answer);
+            print!("{}", MadSkin::default().term_text(&with_links));
+            println!();
+            answer.to_string()
+        }
+        Err(e) => {
+            println!(
+                "{}: {e}",
+                "Error: Failed to get response from LLMService".red().bold()
+            );
+            String::new()
+        }
+    }
+}
 
 /// Streams and prints the assistant's response, returning the accumulated response string.
+/// When `show_reasoning` is set, the reasoning is printed before the answer instead of discarded.
 async fn print_assistant_response_stream(
     llm_service: &LLMService,
-    messages: &Vec<ChatMessage>,
+    messages: &[ChatMessage],
+    searched: Option<&[ChatMessage]>,
+    show_reasoning: bool,
 ) -> String {
     let mut response_accum = String::new();
-    let stream = llm_service.generate_response_stream(messages, None).await;
+    let mut reasoning_accum = String::new();
+    let stream = llm_service
+        .generate_response_stream(messages, searched)
+        .await;
     let mut is_spinner_at_end = false;
     let spinner_frames = ['✴', '✦', '✶', '✺', '✶', '✦', '✴'];
     let mut spinner_index = 0;
@@ -62,6 +277,9 @@ async fn print_assistant_response_stream(
                             if is_thinking {
                                 print!("\r{}", " ".repeat(40)); // Clear spinner line
                                 print!("\r{}", "--- Done!\n".green().bold());
+                                if show_reasoning {
+                                    print_reasoning(&reasoning_accum);
+                                }
                                 is_thinking = false;
                             }
                             response_accum.push_str(&stream_chunk.content);
@@ -180,7 +398,8 @@ async fn print_assistant_response_stream(
                                 is_spinner_at_end = true;
                             }
                         }
-                        genai::chat::ChatStreamEvent::ReasoningChunk(_stream_chunk) => {
+                        genai::chat::ChatStreamEvent::ReasoningChunk(stream_chunk) => {
+                            reasoning_accum.push_str(&stream_chunk.content);
                             if !is_thinking {
                                 print!("\r{}", "--- Thinking... ".yellow().bold());
                                 io::stdout().flush().unwrap();
@@ -215,6 +434,9 @@ async fn print_assistant_response_stream(
             if is_thinking {
                 print!("\r{}", " ".repeat(40)); // Clear spinner line
                 print!("\r{}", "--- Done!\n".green().bold());
+                if show_reasoning {
+                    print_reasoning(&reasoning_accum);
+                }
             }
             println!();
         }
@@ -230,8 +452,145 @@ async fn print_assistant_response_stream(
     response_accum
 }
 
+/// Cuts `text` to `max` characters, noting how much was left out.
+fn truncate_chars(text: &str, max: usize) -> String {
+    let total = text.chars().count();
+    if total <= max {
+        return text.to_string();
+    }
+    let truncated: String = text.chars().take(max).collect();
+    format!(
+        "{}… ({} more chars, /verbose to show all)",
+        truncated.trim_end(),
+        total - max
+    )
+}
+
+/// Formats a tool result for the terminal. Search results become a numbered list,
+/// page content is truncated, and anything else is pretty-printed JSON. `verbose`
+/// shows the full pretty-printed payload instead.
+fn format_tool_result(tool_name: &str, result: &serde_json::Value, verbose: bool) -> String {
+    let pretty = serde_json::to_string_pretty(result).unwrap_or_else(|_| result.to_string());
+    if verbose {
+        return pretty;
+    }
+
+    if tool_name == "ddg_search" {
+        if let Some(results) = result.get("results").and_then(|r| r.as_array()) {
+            let page = result.get("page").and_then(|p| p.as_u64()).unwrap_or(1);
+            let mut out = format!("{} results (page {})", results.len(), page);
+            for (i, item) in results.iter().enumerate() {
+                let field = |name: &str| item.get(name).and_then(|v| v.as_str()).unwrap_or("");
+                out.push_str(&format!(
+                    "\n{:>3}. {}\n     {}",
+                    i + 1,
+                    field("title"),
+                    field("link")
+                ));
+                let snippet = field("snippet").trim();
+                if !snippet.is_empty() {
+                    let snippet: String = if snippet.chars().count() > SEARCH_SNIPPET_WIDTH {
+                        let cut: String = snippet.chars().take(SEARCH_SNIPPET_WIDTH).collect();
+                        format!("{}…", cut.trim_end())
+                    } else {
+                        snippet.to_string()
+                    };
+                    out.push_str(&format!("\n     {}", snippet.dimmed()));
+                }
+            }
+            if result.get("has_more").and_then(|m| m.as_bool()) == Some(true) {
+                out.push_str("\n     (more results available)");
+            }
+            return out;
+        }
+    }
+
+    // Page fetches return one big text field; show its start rather than escaped JSON
+    if let Some(content) = result.get("content").and_then(|c| c.as_str()) {
+        return truncate_chars(content, TOOL_RESULT_PREVIEW_CHARS);
+    }
+
+    truncate_chars(&pretty, TOOL_RESULT_PREVIEW_CHARS)
+}
+
+/// Runs `tool` with `args` straight from the prompt, without involving the model, and
+/// prints the result the same way tool results are shown in a conversation.
+async fn run_tool_directly(tool: &dyn AiTool, args: serde_json::Value, verbose: bool) {
+    let result = match tokio::time::timeout(
+        TOOL_TIMEOUT,
+        tool.execute(&args, &ToolContext::default()),
+    )
+    .await
+    {
+        Ok(result) => result,
+        Err(_) => Err(ToolError::Timeout.into()),
+    };
+    match result {
+        Ok(value) => println!(
+            "{}\n{}",
+            format!("[Tool `{}` returned:]", tool.name())
+                .yellow()
+                .bold(),
+            format_tool_result(tool.name(), &value, verbose)
+        ),
+        Err(e) => println!(
+            "{}",
+            format!("! error ! Error executing tool `{}`: {}", tool.name(), e)
+                .red()
+                .bold()
+        ),
+    }
+}
+
+/// Prints the tool calls the bot made while replying to the post at `uri`, as stored
+/// in its memory collection.
+async fn print_tool_trace(uri: &str) -> Result<()> {
+    let vdb = QdrantEnv::from_env()?.connect().await?;
+    let Some(entry) = vdb.get(&tool_trace_id(uri)).await? else {
+        println!(
+            "{}",
+            "No tool calls stored for that post. The bot may have replied without tools.".magenta()
+        );
+        return Ok(());
+    };
+
+    let steps: Vec<serde_json::Value> = serde_json::from_str(&entry.content)?;
+    println!(
+        "{}",
+        format!("{} tool calls led to the reply:", steps.len())
+            .magenta()
+            .bold()
+    );
+    for (i, step) in steps.iter().enumerate() {
+        let tool = step.get("tool").and_then(|t| t.as_str()).unwrap_or("?");
+        let args = step.get("args").map(|a| a.to_string()).unwrap_or_default();
+        let result = step.get("result").and_then(|r| r.as_str()).unwrap_or("");
+        println!("{:>3}. {} {}", i + 1, tool.bold(), args.dimmed());
+        println!("     {}", truncate_chars(result, TOOL_RESULT_PREVIEW_CHARS));
+    }
+    Ok(())
+}
+
 /// Runs the CLI mode for interacting with the LLMService.
-pub async fn run_cli() -> Result<()> {
+/// Prints each tool with the first line of its description.
+fn print_tools(llm: &LLMService) {
+    println!("Available tools:");
+    for (name, description) in llm.list_tools_detailed() {
+        let summary = description.lines().next().unwrap_or_default().trim();
+        let summary = if summary.chars().count() > TOOL_DESCRIPTION_WIDTH {
+            let truncated: String = summary.chars().take(TOOL_DESCRIPTION_WIDTH).collect();
+            format!("{}…", truncated.trim_end())
+        } else {
+            summary.to_string()
+        };
+        println!("- {}: {}", name.bold(), summary);
+    }
+}
+
+pub async fn run_cli(options: CliOptions) -> Result<()> {
+    let mut show_reasoning = options.show_reasoning;
+    let no_stream = options.no_stream;
+
     // get formatted current time (to provide to the LLMService)
     let current_time =
         time::OffsetDateTime::now_utc().format(&time::format_description::well_known::Rfc3339)?;
@@ -241,36 +600,104 @@ pub async fn run_cli() -> Result<()> {
         current_time
     );
 
-    // load the system prompt from 'prompt.txt' if it exists
-    let prompt_path: &str = "prompt_cli.txt";
-    let prompt_string = std::fs::read_to_string(prompt_path).ok();
+    // load the system prompt from --prompt-file, or 'prompt_cli.txt' if it exists
+    let prompt_file = match &options.prompt_file {
+        Some(path) => Some(std::fs::read_to_string(path).map_err(|e| {
+            anyhow::anyhow!("Could not read prompt file {}: {}", path.display(), e)
+        })?),
+        None => std::fs::read_to_string(PROMPT_PATH).ok(),
+    };
+    let prompt_path = options
+        .prompt_file
+        .as_deref()
+        .unwrap_or(Path::new(PROMPT_PATH));
+    let prompt_string = prompt_file.filter(|prompt| {
+        let blank = prompt.trim().is_empty();
+        if blank {
+            println!(
+                "{}",
+                format!(
+                    "! warning ! {} is empty, using the default prompt.",
+                    prompt_path.display()
+                )
+                .yellow()
+                .bold()
+            );
+        }
+        !blank
+    });
     let mut system_prompt: Option<&str> = prompt_string.as_deref();
 
-    if let None = system_prompt {
+    if system_prompt.is_none() {
         system_prompt = Some(&default_prompt);
     }
 
+    // Pick the model from --model or the environment, falling back to DeepSeek
+    let model = options
+        .model
+        .clone()
+        .or_else(|| std::env::var("MODEL").ok())
+        .unwrap_or_else(|| DEFAULT_MODEL.to_string());
+
     // Initialize LLMService with tools
     let mut llm_service = LLMService::new(
         system_prompt,
         vec![
             Box::new(MathTool),
             Box::new(DDGSearchTool),
-            Box::new(WebsiteTool),
+            Box::new(WebsiteTool::from_env()),
+            Box::new(DictionaryTool),
+            Box::new(GithubTool::from_env()),
+            Box::new(PriceTool::from_env()),
+            Box::new(TranslateTool::from_env()),
+            Box::new(OcrTool::from_env()),
+            Box::new(SummarizeTool::with_model(&model)?),
         ],
-        "DeepSeek-R1-0528",
+        &model,
     )?;
 
     println!("Welcome to the Aigis CLI!");
+    println!("Using model: {}", llm_service.model());
     println!(
         "Type your messages below. Type 'exit' to quit or use slash commands (e.g., /command) to manage settings."
     );
-    println!("Available tools:");
-    for tool in llm_service.list_tools() {
-        println!("- {}", tool);
+
+    // Turn off the tools disabled in a previous session
+    let mut disabled_tools: Vec<Box<dyn AiTool>> = Vec::new();
+    match CliSettings::load(Path::new(SETTINGS_PATH)) {
+        Ok(Some(settings)) => {
+            for name in &settings.disabled_tools {
+                match llm_service.take_tool(name) {
+                    Some(tool) => disabled_tools.push(tool),
+                    None => println!(
+                        "{}",
+                        format!(
+                            "! warning ! {} disables unknown tool `{}`.",
+                            SETTINGS_PATH, name
+                        )
+                        .yellow()
+                        .bold()
+                    ),
+                }
+            }
+        }
+        Ok(None) => {}
+        Err(e) => println!(
+            "{}",
+            format!("! warning ! Could not load {}: {}", SETTINGS_PATH, e)
+                .yellow()
+                .bold()
+        ),
     }
+
+    print_tools(&llm_service);
     println!();
 
+    let memory = CliMemory::from_env().await?;
+    if memory.is_some() {
+        println!("{}", "Long-term memory is on.".magenta());
+    }
+
     let mut messages = vec![];
 
     loop {
@@ -297,7 +724,63 @@ pub async fn run_cli() -> Result<()> {
                         "{}",
                         "  /set_prompt <new_prompt> - Change the system prompt.".magenta()
                     );
+                    println!(
+                        "{}",
+                        format!(
+                            "  /model [name] - Show or switch the model (current: {}).",
+                            llm_service.model()
+                        )
+                        .magenta()
+                    );
+                    println!(
+                        "{}",
+                        "  /show_prompt - Show the effective system prompt.".magenta()
+                    );
+                    println!(
+                        "{}",
+                        format!("  /reload_prompt - Re-read {} from disk.", PROMPT_PATH).magenta()
+                    );
+                    println!(
+                        "{}",
+                        "  /effort [low|medium|high|off] - Show or set the reasoning effort."
+                            .magenta()
+                    );
                     println!("{}", "  /list_tools - List all available tools.".magenta());
+                    println!(
+                        "{}",
+                        "  /disable_tool <name> - Stop offering a tool to the model.".magenta()
+                    );
+                    println!(
+                        "{}",
+                        "  /enable_tool <name> - Offer a disabled tool again.".magenta()
+                    );
+                    println!(
+                        "{}",
+                        format!(
+                            "  /save_settings - Save the disabled tools to {} for next time.",
+                            SETTINGS_PATH
+                        )
+                        .magenta()
+                    );
+                    println!(
+                        "{}",
+                        "  /search <query> - Run a web search directly, without the model."
+                            .magenta()
+                    );
+                    println!(
+                        "{}",
+                        "  /fetch <url> - Fetch a page as markdown, without the model.".magenta()
+                    );
+                    println!(
+                        "{}",
+                        "  /why <post uri> - Show the tool calls behind the bot's reply to a post."
+                            .magenta()
+                    );
+                    println!(
+                        "{}",
+                        "  /verbose - Toggle showing the model's reasoning and full tool results."
+                            .magenta()
+                    );
                     println!("{}", "  /exit - Exit the CLI.".magenta());
                 }
                 cmd if cmd.starts_with("set_prompt ") => {
@@ -305,12 +788,157 @@ pub async fn run_cli() -> Result<()> {
                     llm_service.set_system_prompt(new_prompt);
                     println!("{}", "System prompt updated.".magenta());
                 }
+                "show_prompt" => {
+                    println!("{}", "Current system prompt:".magenta().bold());
+                    let prompt = llm_service.system_prompt();
+                    println!("{}", if prompt.is_empty() { "<none>" } else { &prompt });
+                }
+                "reload_prompt" => match std::fs::read_to_string(PROMPT_PATH) {
+                    Ok(new_prompt) => {
+                        llm_service.set_system_prompt(new_prompt);
+                        println!(
+                            "{}",
+                            format!("System prompt reloaded from {}.", PROMPT_PATH).magenta()
+                        );
+                    }
+                    Err(e) => {
+                        println!(
+                            "{}",
+                            format!("! error ! Could not read {}: {}", PROMPT_PATH, e)
+                                .red()
+                                .bold()
+                        );
+                    }
+                },
+                "model" => {
+                    println!(
+                        "{}",
+                        format!("Current model: {}", llm_service.model()).magenta()
+                    );
+                }
+                cmd if cmd.starts_with("model ") => {
+                    let new_model = cmd.trim_start_matches("model ").trim();
+                    if new_model.is_empty() {
+                        println!("{}", "! error ! Usage: /model [name]".red().bold());
+                    } else {
+                        llm_service.set_model(new_model);
+                        println!("{}", format!("Switched model to {}.", new_model).magenta());
+                    }
+                }
+                "verbose" => {
+                    show_reasoning = !show_reasoning;
+                    let state = if show_reasoning { "on" } else { "off" };
+                    println!(
+                        "{}",
+                        format!("Reasoning and full tool results {}.", state).magenta()
+                    );
+                }
+                "effort" => {
+                    let effort = llm_service
+                        .reasoning_effort()
+                        .map(|e| e.to_lower_str())
+                        .unwrap_or("off");
+                    println!("{}", format!("Reasoning effort: {}.", effort).magenta());
+                }
+                cmd if cmd.starts_with("effort ") => {
+                    match cmd.trim_start_matches("effort ").trim() {
+                        "off" => {
+                            llm_service.set_reasoning_effort(None);
+                            println!("{}", "Reasoning effort left to the provider.".magenta());
+                        }
+                        level => match ReasoningEffort::from_lower_str(level) {
+                            Some(effort) => {
+                                llm_service.set_reasoning_effort(Some(effort));
+                                println!(
+                                    "{}",
+                                    format!("Reasoning effort set to {}.", level).magenta()
+                                );
+                            }
+                            None => println!(
+                                "{}",
+                                "! error ! Expected low, medium, high or off.".red().bold()
+                            ),
+                        },
+                    }
+                }
                 "list_tools" => {
-                    println!("Available tools:");
-                    for tool in llm_service.list_tools() {
-                        println!("- {}", tool);
+                    print_tools(&llm_service);
+                    if !disabled_tools.is_empty() {
+                        let names: Vec<&str> = disabled_tools.iter().map(|t| t.name()).collect();
+                        println!("Disabled: {}", names.join(", "));
+                    }
+                }
+                cmd if cmd.starts_with("disable_tool ") => {
+                    let name = cmd.trim_start_matches("disable_tool ").trim();
+                    match llm_service.take_tool(name) {
+                        Some(tool) => {
+                            disabled_tools.push(tool);
+                            println!("{}", format!("Disabled tool `{}`.", name).magenta());
+                        }
+                        None => println!(
+                            "{}",
+                            format!("! error ! No enabled tool named `{}`.", name)
+                                .red()
+                                .bold()
+                        ),
+                    }
+                }
+                cmd if cmd.starts_with("enable_tool ") => {
+                    let name = cmd.trim_start_matches("enable_tool ").trim();
+                    match disabled_tools.iter().position(|t| t.name() == name) {
+                        Some(pos) => {
+                            llm_service.add_tool(disabled_tools.remove(pos));
+                            println!("{}", format!("Enabled tool `{}`.", name).magenta());
+                        }
+                        None => println!(
+                            "{}",
+                            format!("! error ! No disabled tool named `{}`.", name)
+                                .red()
+                                .bold()
+                        ),
+                    }
+                }
+                "save_settings" => {
+                    let settings = CliSettings {
+                        disabled_tools: disabled_tools
+                            .iter()
+                            .map(|t| t.name().to_string())
+                            .collect(),
+                    };
+                    match settings.save(Path::new(SETTINGS_PATH)) {
+                        Ok(()) => println!(
+                            "{}",
+                            format!("Settings saved to {}.", SETTINGS_PATH).magenta()
+                        ),
+                        Err(e) => println!(
+                            "{}",
+                            format!("! error ! Could not save {}: {}", SETTINGS_PATH, e)
+                                .red()
+                                .bold()
+                        ),
+                    }
+                }
+                cmd if cmd.starts_with("search ") => {
+                    let query = cmd.trim_start_matches("search ").trim();
+                    let args = serde_json::json!({ "query": query });
+                    run_tool_directly(&DDGSearchTool, args, show_reasoning).await;
+                }
+                cmd if cmd.starts_with("why ") => {
+                    let uri = cmd.trim_start_matches("why ").trim();
+                    if let Err(e) = print_tool_trace(uri).await {
+                        println!(
+                            "{}",
+                            format!("! error ! Could not load the tool trace: {}", e)
+                                .red()
+                                .bold()
+                        );
                     }
                 }
+                cmd if cmd.starts_with("fetch ") => {
+                    let url = cmd.trim_start_matches("fetch ").trim();
+                    let args = serde_json::json!({ "website": url });
+                    run_tool_directly(&WebsiteTool::from_env(), args, show_reasoning).await;
+                }
                 "exit" => {
                     println!("Goodbye!");
                     break;
@@ -328,55 +956,68 @@ pub async fn run_cli() -> Result<()> {
             // Add user message to conversation
             messages.push(ChatMessage::user(input.to_string()));
 
+            // Pull in similar past exchanges when memory is on
+            let searched = match &memory {
+                Some(memory) => memory.recall(input).await.unwrap_or_else(|e| {
+                    println!(
+                        "{}",
+                        format!("! warning ! Could not recall memories: {}", e)
+                            .yellow()
+                            .bold()
+                    );
+                    Vec::new()
+                }),
+                None => Vec::new(),
+            };
+            let searched = (!searched.is_empty()).then_some(searched.as_slice());
+
             // Streaming response with tool call support
-            use logi::tools::{execute_tool_calls, parse_tool_calls};
+            use logi::tools::{
+                execute_tool_calls, parse_tool_calls, strip_tool_calls, ToolLoopGuard,
+            };
 
             // Use the helper function for initial assistant response
             let stream_messages = messages.clone();
-            let mut response_accum =
-                print_assistant_response_stream(&llm_service, &stream_messages).await;
+            let mut response_accum = print_assistant_response(
+                &llm_service,
+                &stream_messages,
+                searched,
+                show_reasoning,
+                no_stream,
+            )
+            .await;
 
             // Tool call detection after streaming, now allowing possibly infinite chaining
-            let mut last_tool_call: Option<(String, serde_json::Value)> = None;
-            let mut last_tool_call_times = 0;
+            let mut loop_guard = ToolLoopGuard::from_env();
+            let mut final_answer = String::new();
             loop {
                 let tool_calls = parse_tool_calls(&response_accum);
                 if !tool_calls.is_empty() {
-                    // Check for repeated tool call (name + args) for the first tool call only
-                    let first_call = &tool_calls[0];
-                    if let Some((last_name, last_args)) = &last_tool_call {
-                        if last_name == &first_call.tool_name && last_args == &first_call.tool_args
-                        {
-                            if last_tool_call_times >= TOOL_CALL_TIMES {
-                                println!(
-                                    "{}",
-                                    "! error ! Too many repeated tool calls, breaking to avoid infinite loop."
-                                        .red()
-                                        .bold()
-                                );
-                                break;
-                            }
-                            last_tool_call_times += 1;
+                    // Check for repeated tool calls
+                    if !loop_guard.check(&tool_calls) {
+                        let reason = if loop_guard.hit_iteration_cap() {
+                            "! error ! Too many tool call iterations, breaking to limit cost."
                         } else {
-                            last_tool_call_times = 1; // Reset count for new tool call
-                        }
+                            "! error ! Too many repeated tool calls, breaking to avoid infinite loop."
+                        };
+                        println!("{}", reason.red().bold());
+                        break;
                     }
-                    last_tool_call =
-                        Some((first_call.tool_name.clone(), first_call.tool_args.clone()));
 
                     // Execute all tool calls in order
+                    let tool_ctx = ToolContext::default();
                     let tool_results = futures::executor::block_on(execute_tool_calls(
                         &tool_calls,
-                        &llm_service.tools,
+                        llm_service.tools(),
+                        &tool_ctx,
                     ));
                     for (tool_name, result) in &tool_results {
                         match result {
                             Ok(tool_result) => {
                                 println!(
-                                    "\n{}",
-                                    format!("[Tool `{}` returned: {}]", tool_name, tool_result)
-                                        .yellow()
-                                        .bold()
+                                    "\n{}\n{}",
+                                    format!("[Tool `{}` returned:]", tool_name).yellow().bold(),
+                                    format_tool_result(tool_name, tool_result, show_reasoning)
                                 );
                                 // Feed tool result back into conversation and stream follow-up
                                 messages.push(ChatMessage::assistant(response_accum.clone()));
@@ -404,19 +1045,62 @@ pub async fn run_cli() -> Result<()> {
                             }
                         }
                     }
+                    // Stop if the model keeps calling tools that don't exist, or repeats
+                    // a call whose arguments were already rejected
+                    if !loop_guard.check_results(&tool_calls, &tool_results) {
+                        println!(
+                            "{}",
+                            "! error ! Model keeps making failing tool calls, falling back to a plain answer."
+                                .red()
+                                .bold()
+                        );
+                        let fallback = strip_tool_calls(&response_accum);
+                        if !fallback.is_empty() {
+                            println!("{} {}", "Assistant:".green().bold(), fallback);
+                        }
+                        final_answer = fallback.clone();
+                        messages.push(ChatMessage::assistant(fallback));
+                        break;
+                    }
                     // Use the helper function for follow-up assistant response
-                    let followup_accum =
-                        print_assistant_response_stream(&llm_service, &messages).await;
+                    let followup_accum = print_assistant_response(
+                        &llm_service,
+                        &messages,
+                        searched,
+                        show_reasoning,
+                        no_stream,
+                    )
+                    .await;
                     messages.push(ChatMessage::assistant(followup_accum.clone()));
                     // Prepare for next loop iteration
                     response_accum = followup_accum;
                     continue;
                 } else {
                     // No tool call, just add the response to the conversation
+                    if sanitize_output(&response_accum).1 {
+                        println!(
+                            "{}",
+                            "! warning ! This response contains words from the output wordlist."
+                                .yellow()
+                                .bold()
+                        );
+                    }
+                    final_answer = response_accum.clone();
                     messages.push(ChatMessage::assistant(response_accum));
                     break;
                 }
             }
+
+            if let Some(memory) = &memory {
+                if let Err(e) = memory.remember(input, &final_answer).await {
+                    println!(
+                        "{}",
+                        format!("! warning ! Could not store this exchange: {}", e)
+                            .yellow()
+                            .bold()
+                    );
+                }
+            }
         }
     }
 
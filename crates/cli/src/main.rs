@@ -1,10 +1,19 @@
 use anyhow::Result;
 
 mod cli;
-use cli::run_cli;
+mod memory;
+mod settings;
+use cli::{export_conversation, reset_memory, run_cli, CliOptions};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenvy::dotenv().ok();
-    run_cli().await
+    let options = CliOptions::from_args();
+    if options.reset_memory {
+        return reset_memory().await;
+    }
+    if let Some(conversation) = &options.export {
+        return export_conversation(conversation, options.export_file.as_deref()).await;
+    }
+    run_cli(options).await
 }
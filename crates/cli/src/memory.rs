@@ -0,0 +1,158 @@
+use anyhow::Result;
+use genai::chat::ChatMessage;
+use logi::embed::Embedder;
+use logi::vdb::{CollectionTuning, MemoryEntry, MemoryStore};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DEFAULT_MEMORY_TOP_K: usize = 2; // Same as the bot's default
+
+/// Where the memory collection lives, from `QDRANT_URL`, `QDRANT_DB` and `EMBEDDING_DIM`.
+pub struct QdrantEnv {
+    pub url: String,
+    pub collection: String,
+    pub embedding_dim: usize,
+}
+
+impl QdrantEnv {
+    pub fn from_env() -> Result<Self> {
+        let url = std::env::var("QDRANT_URL").map_err(|_| anyhow::anyhow!("QDRANT_URL not set"))?;
+        let collection = std::env::var("QDRANT_DB").unwrap_or("aigis-db".to_string());
+        let embedding_dim = std::env::var("EMBEDDING_DIM")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1536);
+        Ok(Self {
+            url,
+            collection,
+            embedding_dim,
+        })
+    }
+
+    pub async fn connect(&self) -> Result<MemoryStore> {
+        MemoryStore::new(
+            &self.url,
+            &self.collection,
+            self.embedding_dim,
+            CollectionTuning::from_env(),
+        )
+        .await
+    }
+}
+
+/// Long-term memory for a CLI session, retrieved and stored the same way the bot does.
+pub struct CliMemory {
+    vdb: MemoryStore,
+    emb: Embedder,
+    conversation_id: String,
+    top_k: usize,
+}
+
+impl CliMemory {
+    /// Connects when `CLI_MEMORY` is `1` or `true`; returns `None` otherwise.
+    /// `MEMORY_TOP_K` sets how many memories are recalled per turn.
+    pub async fn from_env() -> Result<Option<Self>> {
+        let enabled = std::env::var("CLI_MEMORY")
+            .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true"))
+            .unwrap_or(false);
+        if !enabled {
+            return Ok(None);
+        }
+
+        let vdb = QdrantEnv::from_env()?.connect().await?;
+        let emb = Embedder::new()?;
+        let top_k = std::env::var("MEMORY_TOP_K")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MEMORY_TOP_K);
+        // One conversation per CLI session
+        let conversation_id = uuid::Uuid::new_v5(
+            &uuid::Uuid::NAMESPACE_OID,
+            format!("cli#{}", unix_now_nanos()).as_bytes(),
+        )
+        .to_string();
+
+        Ok(Some(Self {
+            vdb,
+            emb,
+            conversation_id,
+            top_k,
+        }))
+    }
+
+    /// Finds past exchanges similar to `input`, as messages to pass as `searched_messages`.
+    pub async fn recall(&self, input: &str) -> Result<Vec<ChatMessage>> {
+        let Some(query) = self.emb.embed(vec![input.to_string()])?.pop() else {
+            return Ok(Vec::new());
+        };
+        let mut similar = self
+            .vdb
+            .get_similar(None, query, Some(vec!["stm".to_string()]), self.top_k, None)
+            .await?;
+        similar.dedup_by_key(|entry| entry.id.clone());
+
+        Ok(similar
+            .into_iter()
+            .map(|entry| match entry.role.as_str() {
+                "assistant" => ChatMessage::assistant(entry.content),
+                _ => ChatMessage::user(entry.content),
+            })
+            .collect())
+    }
+
+    /// Stores the user's input and the final answer as two linked memories.
+    pub async fn remember(&self, input: &str, answer: &str) -> Result<()> {
+        if answer.trim().is_empty() {
+            return Ok(());
+        }
+        let mut vectors = self
+            .emb
+            .embed(vec![input.to_string(), answer.to_string()])?
+            .into_iter();
+        let (Some(input_vec), Some(answer_vec)) = (vectors.next(), vectors.next()) else {
+            return Ok(());
+        };
+
+        let nanos = unix_now_nanos();
+        let timestamp = (nanos / 1_000_000_000) as i64;
+        let id = |suffix: &str| {
+            uuid::Uuid::new_v5(
+                &uuid::Uuid::NAMESPACE_OID,
+                format!("{}#{}#{}", self.conversation_id, nanos, suffix).as_bytes(),
+            )
+            .to_string()
+        };
+
+        self.vdb
+            .put_batch(vec![
+                MemoryEntry {
+                    id: id("user"),
+                    content: input.to_string(),
+                    embedding: input_vec,
+                    // the input must sort before the answer
+                    timestamp: timestamp - 1,
+                    tags: vec!["stm".to_string()],
+                    role: "user".to_string(),
+                    entry_type: "cli_message".to_string(),
+                    conversation_id: self.conversation_id.clone(),
+                },
+                MemoryEntry {
+                    id: id("assistant"),
+                    content: answer.to_string(),
+                    embedding: answer_vec,
+                    timestamp,
+                    tags: vec!["stm".to_string()],
+                    role: "assistant".to_string(),
+                    entry_type: "cli_reply".to_string(),
+                    conversation_id: self.conversation_id.clone(),
+                },
+            ])
+            .await
+    }
+}
+
+fn unix_now_nanos() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
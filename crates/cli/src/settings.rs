@@ -0,0 +1,31 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Where `/save_settings` writes to and settings are loaded from at startup.
+pub const SETTINGS_PATH: &str = "cli_settings.json";
+
+/// CLI preferences kept across sessions.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CliSettings {
+    /// Tools turned off with `/disable_tool`. Disabled tools are stored rather than
+    /// enabled ones, so tools added in later versions start out on.
+    #[serde(default)]
+    pub disabled_tools: Vec<String>,
+}
+
+impl CliSettings {
+    /// Reads the settings at `path`, or `None` if nothing was saved yet.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        match std::fs::read_to_string(path) {
+            Ok(json) => Ok(Some(serde_json::from_str(&json)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::Result;
+use atrium_api::app::bsky::graph::get_list;
+use atrium_api::types::LimitedNonZeroU8;
+use bsky_sdk::BskyAgent;
+use tracing::{info, warn};
+
+/// Default time between refreshes of an `ALLOWED_LIST_URI` list.
+pub const DEFAULT_LIST_REFRESH_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Who the bot may reply to: static DIDs, members of a Bluesky list, or both.
+///
+/// With neither configured everyone is allowed. Otherwise a DID is allowed if it is
+/// in either set. List membership starts empty and is filled in by a background task,
+/// so until the first fetch succeeds only the static DIDs are allowed.
+#[derive(Debug, Default)]
+pub struct Allowlist {
+    users: Option<HashSet<String>>,
+    list_members: Option<Arc<RwLock<HashSet<String>>>>,
+}
+
+impl Allowlist {
+    pub fn new(users: Option<Vec<String>>) -> Self {
+        Self {
+            users: users.map(|users| users.into_iter().collect()),
+            list_members: None,
+        }
+    }
+
+    /// Also allows the members of the `app.bsky.graph.list` at `list_uri`, fetched now
+    /// and then every `refresh`. Must be called from within a Tokio runtime.
+    pub fn with_list(mut self, agent: BskyAgent, list_uri: String, refresh: Duration) -> Self {
+        let members = Arc::new(RwLock::new(HashSet::new()));
+        spawn_list_refresh(agent, list_uri, refresh, members.clone());
+        self.list_members = Some(members);
+        self
+    }
+
+    /// Whether either `ALLOWED_USERS` or `ALLOWED_LIST_URI` restricts who is allowed.
+    pub fn is_configured(&self) -> bool {
+        self.users.is_some() || self.list_members.is_some()
+    }
+
+    pub fn is_allowed(&self, did: &str) -> bool {
+        if !self.is_configured() {
+            return true;
+        }
+        self.users.as_ref().is_some_and(|users| users.contains(did))
+            || self
+                .list_members
+                .as_ref()
+                .is_some_and(|members| members.read().unwrap().contains(did))
+    }
+}
+
+/// Keeps `members` in sync with the list at `list_uri`. A failed refresh keeps the
+/// previous membership rather than locking everyone out.
+fn spawn_list_refresh(
+    agent: BskyAgent,
+    list_uri: String,
+    refresh: Duration,
+    members: Arc<RwLock<HashSet<String>>>,
+) {
+    tokio::spawn(async move {
+        loop {
+            match fetch_list_members(&agent, &list_uri).await {
+                Ok(fetched) => {
+                    info!("Allowlist {} has {} members", list_uri, fetched.len());
+                    *members.write().unwrap() = fetched;
+                }
+                Err(e) => warn!("Failed to refresh allowlist {}: {}", list_uri, e),
+            }
+            tokio::time::sleep(refresh).await;
+        }
+    });
+}
+
+/// Fetches the DIDs of every member of a list, following pagination.
+pub async fn fetch_list_members(agent: &BskyAgent, list_uri: &str) -> Result<HashSet<String>> {
+    let mut members = HashSet::new();
+    let mut cursor = None;
+    loop {
+        let output = agent
+            .api
+            .app
+            .bsky
+            .graph
+            .get_list(
+                get_list::ParametersData {
+                    cursor,
+                    limit: LimitedNonZeroU8::try_from(100).ok(),
+                    list: list_uri.to_string(),
+                }
+                .into(),
+            )
+            .await?;
+        members.extend(output.items.iter().map(|item| item.subject.did.to_string()));
+        match output.cursor.clone() {
+            Some(next) if !output.items.is_empty() => cursor = Some(next),
+            _ => break,
+        }
+    }
+    Ok(members)
+}
@@ -0,0 +1,158 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use atrium_api::app::bsky::feed::post::RecordData;
+use atrium_api::types::string::{Datetime, Language};
+use bsky_sdk::BskyAgent;
+use genai::chat::ChatMessage;
+use tracing::{debug, error, info};
+
+use crate::create_post;
+use crate::embed::Embedder;
+use crate::llm::{AiService, LLMService};
+use crate::post::truncate_reply;
+use crate::prompt::reply_max_chars;
+use crate::reminders::unix_now;
+use crate::sanitize::sanitize_output;
+use crate::vdb::{MemoryEntry, MemoryStore};
+
+const DEFAULT_AUTOPOST_PROMPT: &str = "Write a short, standalone Bluesky post sharing a thought you've had today. Reply with only the text of the post.";
+
+/// Settings for posting on a schedule, independent of anyone mentioning the bot.
+#[derive(Debug, Clone)]
+pub struct AutopostConfig {
+    /// Time between scheduled posts.
+    pub interval: Duration,
+    /// Instruction sent to the model to produce each post.
+    pub prompt: String,
+}
+
+impl AutopostConfig {
+    /// Reads `AUTOPOST_INTERVAL_SECS` and `AUTOPOST_PROMPT`. Returns `None`, leaving
+    /// scheduled posting off, unless a positive interval is set.
+    pub fn from_env() -> Option<Self> {
+        let interval = std::env::var("AUTOPOST_INTERVAL_SECS")
+            .ok()?
+            .parse::<u64>()
+            .ok()
+            .filter(|&secs| secs > 0)?;
+        let prompt = std::env::var("AUTOPOST_PROMPT")
+            .ok()
+            .filter(|p| !p.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_AUTOPOST_PROMPT.to_string());
+
+        Some(Self {
+            interval: Duration::from_secs(interval),
+            prompt,
+        })
+    }
+}
+
+/// Generates and publishes top-level posts on a fixed interval.
+pub struct Autoposter {
+    agent: BskyAgent,
+    config: AutopostConfig,
+    aisvc: LLMService,
+    /// Where published posts are remembered; `None` when memory is disabled or the
+    /// embedder is unavailable.
+    memory: Option<(Arc<Embedder>, Arc<MemoryStore>)>,
+}
+
+impl Autoposter {
+    pub fn new(
+        agent: BskyAgent,
+        config: AutopostConfig,
+        aisvc: LLMService,
+        memory: Option<(Arc<Embedder>, Arc<MemoryStore>)>,
+    ) -> Self {
+        Self {
+            agent,
+            config,
+            aisvc,
+            memory,
+        }
+    }
+
+    /// Runs the schedule in the background. The first post goes out one interval after startup.
+    pub fn spawn(self) {
+        info!("Scheduled posting every {:?}", self.config.interval);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(self.config.interval).await;
+                if let Err(e) = self.post_once().await {
+                    error!("Scheduled post failed: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Generates one post, publishes it and stores it in memory, if that's enabled.
+    pub async fn post_once(&self) -> Result<()> {
+        let response = self
+            .aisvc
+            .generate_response(&[ChatMessage::user(self.config.prompt.clone())], None)
+            .await?;
+
+        // remove <think> tag
+        let text = response
+            .split("</think>")
+            .last()
+            .ok_or(anyhow!("no response outputted?"))?
+            .trim()
+            .to_string();
+        if text.is_empty() {
+            debug!("model produced an empty scheduled post, skipping");
+            return Ok(());
+        }
+        // same wordlist as replies, a post that trips it isn't published
+        if sanitize_output(&text).1 {
+            info!("scheduled post contains filtered words, skipping");
+            return Ok(());
+        }
+        let text = truncate_reply(&text, reply_max_chars());
+
+        create_post(
+            &self.agent,
+            RecordData {
+                created_at: Datetime::now(),
+                embed: None,
+                entities: None,
+                facets: None,
+                labels: None,
+                langs: Some(vec![Language::from_str("en").unwrap()]),
+                reply: None,
+                tags: None,
+                text: text.clone(),
+            },
+        )
+        .await?;
+        info!("Published scheduled post");
+
+        let Some((emb, vdb)) = &self.memory else {
+            return Ok(());
+        };
+        let embedding = emb
+            .embed(vec![text.clone()])?
+            .pop()
+            .ok_or(anyhow!("embedder returned no vectors"))?;
+        let timestamp = unix_now();
+        vdb.put(MemoryEntry {
+            id: uuid::Uuid::new_v5(
+                &uuid::Uuid::NAMESPACE_DNS,
+                format!("{}#{}", timestamp, text).as_bytes(),
+            )
+            .to_string(),
+            content: text,
+            embedding,
+            timestamp,
+            tags: vec!["stm".to_string()],
+            role: "assistant".to_string(),
+            entry_type: "autopost".to_string(),
+            conversation_id: uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_DNS, b"autopost")
+                .to_string(),
+        })
+        .await
+    }
+}
@@ -1,22 +1,175 @@
 use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+use ort::environment::GlobalThreadPoolOptions;
+use ort::execution_providers::{
+    CPUExecutionProvider, CUDAExecutionProvider, ExecutionProviderDispatch,
+};
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::{info, warn};
+
+const INIT_ATTEMPTS: u32 = 3; // The first start downloads the model, which can fail transiently
+const INIT_RETRY_BASE_DELAY: Duration = Duration::from_secs(2); // Doubled after each failed attempt
+
+/// Hardware the embedding model runs on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExecutionProvider {
+    #[default]
+    Cpu,
+    /// Requires the `cuda` feature; falls back to CPU if CUDA can't be registered.
+    Cuda,
+}
+
+/// Settings for constructing an [`Embedder`].
+#[derive(Debug, Clone, Default)]
+pub struct EmbedderConfig {
+    /// L2-normalize every output vector. Redundant for Cosine distance, but required
+    /// for Dot distance to behave like cosine similarity.
+    pub normalize: bool,
+    /// Number of ONNX Runtime intra-op threads. `None` uses every available core.
+    pub intra_threads: Option<usize>,
+    /// Which ONNX Runtime execution provider to run the model on.
+    pub execution_provider: ExecutionProvider,
+    /// Where the model is downloaded to and loaded from. `None` uses fastembed's
+    /// `.fastembed_cache` in the working directory, which containers usually lose on
+    /// restart; point this at a persistent volume to skip the download.
+    pub cache_dir: Option<PathBuf>,
+}
+
+impl EmbedderConfig {
+    /// Reads `EMBED_NORMALIZE`, `EMBED_THREADS`, `EMBED_EXECUTION_PROVIDER` (`cpu` or `cuda`)
+    /// and `FASTEMBED_CACHE_DIR`.
+    pub fn from_env() -> Self {
+        let normalize = std::env::var("EMBED_NORMALIZE")
+            .map(|v| v.eq_ignore_ascii_case("true") || v == "1")
+            .unwrap_or(false);
+        let intra_threads = std::env::var("EMBED_THREADS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&n: &usize| n > 0);
+        let execution_provider = match std::env::var("EMBED_EXECUTION_PROVIDER") {
+            Ok(v) if v.eq_ignore_ascii_case("cuda") => ExecutionProvider::Cuda,
+            Ok(v) if !v.eq_ignore_ascii_case("cpu") => {
+                warn!("Unknown EMBED_EXECUTION_PROVIDER '{}', using cpu", v);
+                ExecutionProvider::Cpu
+            }
+            _ => ExecutionProvider::Cpu,
+        };
+        let cache_dir = std::env::var("FASTEMBED_CACHE_DIR")
+            .ok()
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from);
+
+        Self {
+            normalize,
+            intra_threads,
+            execution_provider,
+            cache_dir,
+        }
+    }
+}
 
 pub struct Embedder {
-    embedder: TextEmbedding,
+    backend: Backend,
+    normalize: bool,
+}
+
+enum Backend {
+    Model(Box<TextEmbedding>),
+    /// Returns the same vector for every text, see [`Embedder::stub`].
+    #[cfg(any(test, feature = "test-util"))]
+    Stub(Vec<f32>),
 }
 
 impl Embedder {
+    /// Creates an embedder configured from the environment, see [`EmbedderConfig::from_env`].
     pub fn new() -> anyhow::Result<Self> {
+        Self::with_config(EmbedderConfig::from_env())
+    }
+
+    /// Like [`Embedder::new`], but retries with backoff before giving up. Blocks the
+    /// calling thread between attempts, so it's meant for startup.
+    pub fn new_with_retry() -> anyhow::Result<Self> {
+        let config = EmbedderConfig::from_env();
+        let mut delay = INIT_RETRY_BASE_DELAY;
+        let mut attempt = 1;
+        loop {
+            match Self::with_config(config.clone()) {
+                Err(e) if attempt < INIT_ATTEMPTS => {
+                    warn!(
+                        "Embedder init failed ({}/{}): {}, retrying in {:?}",
+                        attempt, INIT_ATTEMPTS, e, delay
+                    );
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    pub fn with_config(config: EmbedderConfig) -> anyhow::Result<Self> {
+        let execution_providers: Vec<ExecutionProviderDispatch> = match config.execution_provider {
+            ExecutionProvider::Cpu => vec![CPUExecutionProvider::default().build()],
+            ExecutionProvider::Cuda => vec![
+                CUDAExecutionProvider::default().build(),
+                CPUExecutionProvider::default().build(),
+            ],
+        };
+
+        // fastembed always sizes its own session thread pool to every core, so a
+        // thread cap has to go through a global ONNX Runtime pool, which sessions
+        // created afterwards share instead.
+        if let Some(threads) = config.intra_threads {
+            ort::init()
+                .with_global_thread_pool(
+                    GlobalThreadPoolOptions::default().with_intra_threads(threads)?,
+                )
+                .commit()?;
+            info!("Embedder using {} intra-op threads", threads);
+        }
+
+        let mut options = InitOptions::new(EmbeddingModel::ParaphraseMLMiniLML12V2)
+            .with_execution_providers(execution_providers);
+        if let Some(cache_dir) = config.cache_dir {
+            info!("Embedder model cache at {}", cache_dir.display());
+            options = options.with_cache_dir(cache_dir);
+        }
+
         Ok(Self {
-            embedder: TextEmbedding::try_new(InitOptions::new(
-                EmbeddingModel::ParaphraseMLMiniLML12V2,
-            ))?,
+            backend: Backend::Model(Box::new(TextEmbedding::try_new(options)?)),
+            normalize: config.normalize,
         })
     }
+
+    /// An embedder that maps every text to `vector` without loading a model, for tests.
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn stub(vector: Vec<f32>) -> Self {
+        Self {
+            backend: Backend::Stub(vector),
+            normalize: false,
+        }
+    }
+
     pub fn embed(&self, texts: Vec<String>) -> anyhow::Result<Vec<Vec<f32>>> {
-        let embeddings = self
-            .embedder
-            .embed(texts, None)
-            .map_err(|e| anyhow::anyhow!("Failed to embed: {}", e))?;
+        let mut embeddings = match &self.backend {
+            Backend::Model(model) => model
+                .embed(texts, None)
+                .map_err(|e| anyhow::anyhow!("Failed to embed: {}", e))?,
+            #[cfg(any(test, feature = "test-util"))]
+            Backend::Stub(vector) => vec![vector.clone(); texts.len()],
+        };
+        if self.normalize {
+            embeddings.iter_mut().for_each(|v| l2_normalize(v));
+        }
         Ok(embeddings)
     }
 }
+
+/// Scales a vector to unit length in place. Zero vectors are left untouched.
+fn l2_normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+}
@@ -0,0 +1,2348 @@
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use atrium_api::{
+    app::bsky::feed::{
+        defs::{PostViewData, ThreadViewPostData},
+        get_post_thread, get_posts,
+        post::ReplyRefData,
+    },
+    com::atproto::repo::strong_ref::MainData,
+    types::{
+        string::{Cid, Datetime, Did, Language},
+        LimitedU16, Object, TryFromUnknown,
+    },
+};
+use bsky_sdk::BskyAgent;
+use futures_util::StreamExt;
+use genai::chat::{ChatMessage, ChatStreamEvent, ToolResponse};
+use multibase::Base;
+use rand::Rng;
+use rocketman::{
+    ingestion::LexiconIngestor,
+    types::event::{Commit, Event, Kind, Operation},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::watch;
+use tracing::{debug, error, info, trace, warn, Instrument};
+
+use crate::allowlist::{Allowlist, DEFAULT_LIST_REFRESH_INTERVAL};
+use crate::embed::Embedder;
+use crate::intent::IntentRouter;
+use crate::lang::resolve_reply_langs;
+use crate::llm::{AiService, LLMService, DEFAULT_MODEL};
+use crate::post::{
+    truncate_reply, EmbeddingFields, PostData, PostEmbed, PostEmbedExternal, PostEmbedImage,
+    PostEmbedImages, PostEmbedMedia, PostEmbedRecord, PostEmbedRecordWithMedia, PostEmbedVideo,
+};
+use crate::prompt::{default_persona, reply_max_chars, BLUESKY_MAX_GRAPHEMES};
+use crate::reminders::{ReminderStore, REPLY_TARGET};
+use crate::sanitize::sanitize_output;
+use crate::thread_cache::ThreadCache;
+use crate::tools::attach_image::{AttachImageTool, ReplyImage, REPLY_IMAGES};
+use crate::tools::calc::MathTool;
+use crate::tools::dictionary::DictionaryTool;
+use crate::tools::github::GithubTool;
+use crate::tools::identity::IdentityTool;
+use crate::tools::ocr::OcrTool;
+use crate::tools::price::PriceTool;
+use crate::tools::remember::{RememberTool, POSTER_DID};
+use crate::tools::reminder::ReminderTool;
+use crate::tools::search::DDGSearchTool;
+use crate::tools::summarize::SummarizeTool;
+use crate::tools::translate::TranslateTool;
+use crate::tools::website::WebsiteTool;
+use crate::tools::{
+    execute_tool_calls, has_complete_tool_calls, parse_tool_calls, strip_tool_calls, AiTool,
+    ToolContext, ToolLoopGuard,
+};
+use crate::vdb::{MemoryEntry, MemoryStore};
+use crate::{
+    create_post, env_flag, EMBEDDER_DEGRADED, EMBED_LATENCY, INGEST_ERRORS, INGEST_LATENCY,
+    LLM_FIRST_TOKEN_LATENCY, LLM_LATENCY, POSTS_INGESTED, REPLIES_POSTED, THREAD_FETCH_LATENCY,
+    TOOL_LOOP_ITERATIONS, VDB_SEARCH_LATENCY,
+};
+
+const DEFAULT_MEMORY_TOP_K: usize = 2; // Similar memories retrieved per reply
+const DEFAULT_MIN_THREAD_POSTS: usize = 1; // The post itself is enough context by default
+const THREAD_PARENT_HEIGHT: u16 = 80; // Bluesky's own default, well past where the prompt stops helping
+const MAX_SCANNED_FACETS: usize = 64; // A 300-grapheme post can't hold more real mentions than this
+const MAX_TRACE_RESULT_CHARS: usize = 2000; // Whole fetched pages would bloat the stored trace
+const PERSONA_COMMAND: &str = "/persona"; // Allowlisted users set a thread's persona with "/persona <text>"
+const MAX_PERSONA_CHARS: usize = 1000; // Keeps an override from crowding out the rest of the prompt
+
+fn get_blob_ref(blob_ref: &atrium_api::types::BlobRef) -> anyhow::Result<String> {
+    match blob_ref {
+        atrium_api::types::BlobRef::Typed(r) => match r {
+            atrium_api::types::TypedBlobRef::Blob(blob) => blob
+                .r#ref
+                .0
+                .to_string_of_base(Base::Base32Lower)
+                .map_err(|e| anyhow::anyhow!(e)),
+        },
+        atrium_api::types::BlobRef::Untyped(u) => Ok(u.cid.clone()),
+    }
+}
+
+pub struct PostListener {
+    agent: BskyAgent,
+    did: Did,
+    did_string: String,
+    lang: Language,
+    allowlist: Allowlist,
+    aisvc: Arc<tokio::sync::RwLock<LLMService>>, // llm svc, swappable prompt
+    /// Generates replies instead of `aisvc` when set, see [`PostListener::with_ai_service`].
+    responder: Option<Arc<dyn AiService + Send + Sync>>,
+    /// `None` when the embedder failed to load; replies then go out without memory.
+    emb: Option<Arc<Embedder>>,
+    /// `None` when memory is disabled, leaving replies to the thread alone.
+    vdb: Option<Arc<MemoryStore>>,
+    tools: Vec<Box<dyn AiTool>>,
+    timestamps: TimestampStyle,
+    trigger: TriggerMode,
+    memory_top_k: usize,
+    memory_min_score: Option<f32>,
+    retrieval_tags: Vec<String>,
+    embed_quoted: bool,
+    dry_run: bool,
+    skip_media_only: bool,
+    skip_bare_quotes: bool,
+    max_post_age: Option<Duration>,
+    reply_max_chars: usize,
+    min_thread_posts: usize,
+    embedding_fields: EmbeddingFields,
+    reply_delay: Option<ReplyDelay>,
+    intents: IntentRouter,
+    /// Set to `true` when the bot starts shutting down, cutting reply delays short.
+    shutdown: watch::Sender<bool>,
+    threads: ThreadCache,
+}
+
+impl PostListener {
+    /// Without a `vdb`, memory is off: the embedder isn't loaded and nothing is
+    /// retrieved or stored.
+    pub fn new(
+        agent: BskyAgent,
+        did: Did,
+        vdb: Option<MemoryStore>,
+        reminders: Arc<ReminderStore>,
+        system_message: Option<String>,
+        config: ListenerConfig,
+    ) -> Self {
+        let lang = Language::from_str("en").unwrap();
+        let vdb = vdb.map(Arc::new);
+
+        // Losing memory beats crash-looping, so a missing embedder only disables it.
+        // Set FASTEMBED_CACHE_DIR to a persistent volume to skip the model download
+        // on every restart.
+        let emb = if vdb.is_none() {
+            info!("Memory is disabled, replying from the thread alone");
+            None
+        } else {
+            match Embedder::new_with_retry() {
+                Ok(emb) => {
+                    EMBEDDER_DEGRADED.set(0.0);
+                    Some(Arc::new(emb))
+                }
+                Err(e) => {
+                    error!(
+                        error = %e,
+                        "Embedder failed to initialize, replying WITHOUT memory retrieval or storage"
+                    );
+                    EMBEDDER_DEGRADED.set(1.0);
+                    None
+                }
+            }
+        };
+
+        // Initialize tools
+        // Create tools
+        let mut tools: Vec<Box<dyn AiTool>> = vec![
+            Box::new(MathTool),
+            Box::new(DDGSearchTool),
+            Box::new(WebsiteTool::from_env()),
+            Box::new(DictionaryTool),
+            Box::new(GithubTool::from_env()),
+            Box::new(IdentityTool::new()),
+            Box::new(PriceTool::from_env()),
+            Box::new(TranslateTool::from_env()),
+            Box::new(OcrTool::from_env()),
+            Box::new(SummarizeTool::with_model(DEFAULT_MODEL).expect("Summarizer initiated")),
+            Box::new(ReminderTool::new(reminders.clone())),
+        ];
+        info!("Tools initialized: Math, DDGSearch, Website, Dictionary, Github, Identity, Price, Translate, Ocr, SummarizeUrl, Reminder");
+
+        // Create a separate tools vec for the LLM service
+        let mut llm_tools: Vec<Box<dyn AiTool>> = vec![
+            Box::new(MathTool),
+            Box::new(DDGSearchTool),
+            Box::new(WebsiteTool::from_env()),
+            Box::new(DictionaryTool),
+            Box::new(GithubTool::from_env()),
+            Box::new(IdentityTool::new()),
+            Box::new(PriceTool::from_env()),
+            Box::new(TranslateTool::from_env()),
+            Box::new(OcrTool::from_env()),
+            Box::new(SummarizeTool::with_model(DEFAULT_MODEL).expect("Summarizer initiated")),
+            Box::new(ReminderTool::new(reminders)),
+        ];
+
+        if config.image_replies {
+            tools.push(Box::new(AttachImageTool));
+            llm_tools.push(Box::new(AttachImageTool));
+            info!("Tool initialized: AttachImage");
+        }
+
+        // Remembering needs memory and the embedder, so it's only offered with both
+        if let (Some(vdb), Some(emb)) = (&vdb, &emb) {
+            tools.push(Box::new(RememberTool::new(vdb.clone(), emb.clone())));
+            llm_tools.push(Box::new(RememberTool::new(vdb.clone(), emb.clone())));
+            info!("Tool initialized: Remember");
+        }
+
+        let mut aisvc = LLMService::new(
+            Some(&system_message.unwrap_or_else(default_persona)),
+            llm_tools,
+            DEFAULT_MODEL,
+        )
+        .expect("LLM Service initiated");
+        for (name, prompt) in config.intents.prompts() {
+            aisvc.set_named_prompt(name, prompt.clone());
+        }
+        info!("Post listener initialized, ready to listen!");
+        let did_string = did.to_string();
+
+        let mut allowlist = Allowlist::new(config.allowlist);
+        if let Some(list_uri) = config.allowed_list_uri {
+            allowlist = allowlist.with_list(agent.clone(), list_uri, config.allowed_list_refresh);
+        }
+
+        Self {
+            agent,
+            did,
+            did_string,
+            lang,
+            allowlist,
+            aisvc: Arc::new(tokio::sync::RwLock::new(aisvc)),
+            responder: None,
+            emb,
+            vdb,
+            tools,
+            timestamps: config.timestamps,
+            trigger: config.trigger,
+            memory_top_k: config.memory_top_k,
+            memory_min_score: config.memory_min_score,
+            retrieval_tags: config.retrieval_tags,
+            embed_quoted: config.embed_quoted,
+            dry_run: config.dry_run,
+            skip_media_only: config.skip_media_only,
+            skip_bare_quotes: config.skip_bare_quotes,
+            max_post_age: config.max_post_age,
+            reply_max_chars: config.reply_max_chars,
+            min_thread_posts: config.min_thread_posts,
+            embedding_fields: config.embedding_fields,
+            reply_delay: config.reply_delay,
+            intents: config.intents,
+            shutdown: watch::Sender::new(false),
+            threads: ThreadCache::default(),
+        }
+    }
+
+    /// Generates replies with `ai` instead of the listener's own LLM service, e.g. the
+    /// test-util mock. Intent prompts only apply to the listener's own service.
+    pub fn with_ai_service(mut self, ai: Arc<dyn AiService + Send + Sync>) -> Self {
+        self.responder = Some(ai);
+        self
+    }
+
+    /// Sender that tells the listener the bot is shutting down: sending `true` makes
+    /// replies waiting out their delay go out right away.
+    pub fn shutdown_signal(&self) -> watch::Sender<bool> {
+        self.shutdown.clone()
+    }
+
+    /// Waits a random time within the reply delay, or less if the bot starts shutting
+    /// down meanwhile.
+    async fn wait_before_reply(&self) {
+        let Some(delay) = self.reply_delay else {
+            return;
+        };
+        let wait = delay.sample(&mut rand::thread_rng());
+        debug!(?wait, "delaying reply");
+        let mut shutdown = self.shutdown.subscribe();
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {}
+            _ = shutdown.wait_for(|&down| down) => debug!("shutting down, replying now"),
+        }
+    }
+
+    /// The listener's embedder and memory store, for sharing with other tasks that
+    /// remember things. `None` when memory is off or the embedder is unavailable.
+    pub fn shared_memory(&self) -> Option<(Arc<Embedder>, Arc<MemoryStore>)> {
+        Some((self.emb.clone()?, self.vdb.clone()?))
+    }
+
+    /// Shared handle to the listener's LLM service, for changing its prompt while it runs.
+    pub fn llm(&self) -> Arc<tokio::sync::RwLock<LLMService>> {
+        self.aisvc.clone()
+    }
+
+    // Checks whether the post addresses the bot in a way the trigger mode reacts to.
+    // Runs on every post in the firehose, so the cheap checks go first and the facet
+    // scan last, stopping at the first match.
+    fn is_me(&self, post: &atrium_api::app::bsky::feed::post::RecordData) -> bool {
+        (self.trigger.reply && self.is_reply_to_me(post))
+            || (self.trigger.quote && self.quotes_me(post))
+            || (self.trigger.mention && self.mentions_me(post))
+    }
+
+    /// Whether the post is a direct reply to one of the bot's posts.
+    fn is_reply_to_me(&self, post: &atrium_api::app::bsky::feed::post::RecordData) -> bool {
+        post.reply
+            .as_ref()
+            .is_some_and(|reply| reply.parent.uri.contains(&self.did_string))
+    }
+
+    /// Whether the post's facets @-mention the bot.
+    fn mentions_me(&self, post: &atrium_api::app::bsky::feed::post::RecordData) -> bool {
+        post.facets
+            .iter()
+            .flatten()
+            .take(MAX_SCANNED_FACETS)
+            .any(|facet| {
+                facet.data.features.iter().any(|ftr| {
+                matches!(
+                    ftr,
+                    atrium_api::types::Union::Refs(
+                        atrium_api::app::bsky::richtext::facet::MainFeaturesItem::Mention(object),
+                    ) if object.did == self.did
+                )
+            })
+            })
+    }
+
+    /// Whether the post quotes one of the bot's posts.
+    fn quotes_me(&self, post: &atrium_api::app::bsky::feed::post::RecordData) -> bool {
+        self.quoted_post_of_mine(post).is_some()
+    }
+
+    /// The bot's post that `post` quotes, if it quotes one.
+    fn quoted_post_of_mine<'a>(
+        &self,
+        post: &'a atrium_api::app::bsky::feed::post::RecordData,
+    ) -> Option<&'a atrium_api::com::atproto::repo::strong_ref::Main> {
+        let quoted = match &post.embed {
+            Some(atrium_api::types::Union::Refs(e)) => match e {
+                atrium_api::app::bsky::feed::post::RecordEmbedRefs::AppBskyEmbedRecordMain(object) => {
+                    &object.record
+                },
+                atrium_api::app::bsky::feed::post::RecordEmbedRefs::AppBskyEmbedRecordWithMediaMain(object) => {
+                    &object.record.data.record
+                },
+                _ => return None,
+            },
+            _ => return None,
+        };
+        quoted.uri.contains(&self.did_string).then_some(quoted)
+    }
+
+    /// Why `post` should be skipped under the configured stop conditions, if at all.
+    fn stop_condition(
+        &self,
+        post: &atrium_api::app::bsky::feed::post::RecordData,
+    ) -> Option<SkipReason> {
+        use atrium_api::app::bsky::feed::post::RecordEmbedRefs;
+
+        let own_text_is_empty = post
+            .text
+            .split_whitespace()
+            .all(|word| word.starts_with('@'));
+        let embed = match &post.embed {
+            Some(atrium_api::types::Union::Refs(embed)) => Some(embed),
+            _ => None,
+        };
+
+        if self.skip_media_only && own_text_is_empty {
+            let has_media = matches!(
+                embed,
+                Some(
+                    RecordEmbedRefs::AppBskyEmbedImagesMain(_)
+                        | RecordEmbedRefs::AppBskyEmbedVideoMain(_)
+                        | RecordEmbedRefs::AppBskyEmbedRecordWithMediaMain(_)
+                )
+            );
+            if has_media {
+                return Some(SkipReason::MediaOnly);
+            }
+        }
+
+        // Real reposts are a different record type and never reach the listener
+        if self.skip_bare_quotes
+            && own_text_is_empty
+            && matches!(embed, Some(RecordEmbedRefs::AppBskyEmbedRecordMain(_)))
+        {
+            return Some(SkipReason::BareQuote);
+        }
+
+        if let Some(max_age) = self.max_post_age {
+            let created_at = time::OffsetDateTime::parse(
+                post.created_at.as_str(),
+                &time::format_description::well_known::Rfc3339,
+            );
+            if let Ok(created_at) = created_at {
+                let age = time::OffsetDateTime::now_utc() - created_at;
+                if age > max_age {
+                    return Some(SkipReason::TooOld);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Builds the embed of a reply: the quoted post, uploaded images, or both.
+    async fn reply_embed(
+        &self,
+        quoted: Option<atrium_api::com::atproto::repo::strong_ref::Main>,
+        images: Vec<ReplyImage>,
+    ) -> Result<Option<atrium_api::types::Union<atrium_api::app::bsky::feed::post::RecordEmbedRefs>>>
+    {
+        use atrium_api::app::bsky::embed;
+        use atrium_api::app::bsky::feed::post::RecordEmbedRefs;
+        use atrium_api::types::Union;
+
+        let mut uploaded = Vec::new();
+        for image in images {
+            let output = self
+                .agent
+                .api
+                .com
+                .atproto
+                .repo
+                .upload_blob(image.data)
+                .await
+                .map_err(|e| anyhow::anyhow!("failed to upload {} image: {}", image.mime, e))?;
+            uploaded.push(
+                embed::images::ImageData {
+                    alt: image.alt,
+                    aspect_ratio: None,
+                    image: output.data.blob,
+                }
+                .into(),
+            );
+        }
+        let images: Option<embed::images::Main> =
+            (!uploaded.is_empty()).then(|| embed::images::MainData { images: uploaded }.into());
+        let record = quoted
+            .map(|record| -> embed::record::Main { embed::record::MainData { record }.into() });
+
+        Ok(match (record, images) {
+            (None, None) => None,
+            (Some(record), None) => Some(Union::Refs(RecordEmbedRefs::AppBskyEmbedRecordMain(
+                Box::new(record),
+            ))),
+            (None, Some(images)) => Some(Union::Refs(RecordEmbedRefs::AppBskyEmbedImagesMain(
+                Box::new(images),
+            ))),
+            (Some(record), Some(images)) => Some(Union::Refs(
+                RecordEmbedRefs::AppBskyEmbedRecordWithMediaMain(Box::new(
+                    embed::record_with_media::MainData {
+                        media: Union::Refs(
+                            embed::record_with_media::MainMediaRefs::AppBskyEmbedImagesMain(
+                                Box::new(images),
+                            ),
+                        ),
+                        record,
+                    }
+                    .into(),
+                )),
+            )),
+        })
+    }
+
+    /// Streams a response to `messages`, with `memories` as retrieved context, and returns
+    /// its content once complete.
+    ///
+    /// Reasoning that arrives as separate events is dropped rather than accumulated, and
+    /// the time to the first content token is recorded, which for reasoning models is
+    /// most of the wait. Once the answer has started, after any inline `<think>` block,
+    /// it's checked for tool calls as it streams, and a finished batch of calls ends the
+    /// stream early since nothing after it would be used. Whether the answer is empty is
+    /// only known once the stream ends, and is left to the caller.
+    async fn generate(
+        &self,
+        messages: &[ChatMessage],
+        memories: Option<&[ChatMessage]>,
+        prompt: Option<&str>,
+    ) -> Result<String> {
+        let llm = self.aisvc.read().await;
+        let started = Instant::now();
+        let mut stream = match &self.responder {
+            Some(ai) => ai.generate_response_stream(messages, memories).await?,
+            None => {
+                llm.generate_response_stream_with_prompt(prompt, messages, memories)
+                    .await?
+            }
+        };
+
+        let mut content = String::new();
+        while let Some(event) = stream.next().await {
+            let ChatStreamEvent::Chunk(chunk) = event? else {
+                continue;
+            };
+            if content.is_empty() && !chunk.content.is_empty() {
+                LLM_FIRST_TOKEN_LATENCY.record(started.elapsed());
+            }
+            content.push_str(&chunk.content);
+
+            let answer = answer_start(&content).map(|start| &content[start..]);
+            if answer.is_some_and(has_complete_tool_calls) {
+                debug!("tool calls complete, not waiting for the rest of the response");
+                break;
+            }
+        }
+        Ok(content)
+    }
+
+    /// The memory store, unless memory is disabled or the embedder failed to load.
+    fn memory(&self) -> Option<&MemoryStore> {
+        self.emb.as_ref().and(self.vdb.as_deref())
+    }
+
+    /// Embeds `texts`, recording the time spent under the `embed` stage.
+    fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        let _span = tracing::info_span!("embed", count = texts.len()).entered();
+        let Some(emb) = &self.emb else {
+            return Err(anyhow::anyhow!("embedder is unavailable"));
+        };
+        let timer = Instant::now();
+        let result = emb.embed(texts);
+        EMBED_LATENCY.record(timer.elapsed());
+        result
+    }
+
+    /// Embeds `texts` in order, reusing vectors already in `embedded` and embedding
+    /// each remaining distinct text once. New vectors are added to `embedded`, so one
+    /// map shared across an ingest never embeds the same string twice.
+    fn embed_unique(
+        &self,
+        texts: Vec<String>,
+        embedded: &mut HashMap<String, Vec<f32>>,
+    ) -> Result<Vec<Vec<f32>>> {
+        let mut seen = HashSet::new();
+        let missing: Vec<String> = texts
+            .iter()
+            .filter(|text| !embedded.contains_key(*text) && seen.insert(*text))
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            let vectors = self.embed(missing.clone())?;
+            embedded.extend(missing.into_iter().zip(vectors));
+        }
+        // stops at the first text the embedder returned no vector for, like `embed`
+        Ok(texts
+            .iter()
+            .map_while(|text| embedded.get(text).cloned())
+            .collect())
+    }
+
+    fn is_allowlisted(&self, did: &str) -> bool {
+        self.allowlist.is_allowed(did)
+    }
+
+    /// Whether `did` may change a thread's persona. Without a configured allowlist
+    /// everyone could, so nobody does.
+    fn may_set_persona(&self, did: &str) -> bool {
+        self.allowlist.is_configured() && self.allowlist.is_allowed(did)
+    }
+
+    /// Returns the persona override stored for `conversation_id`, if any. Lookup
+    /// failures are logged and treated as no override.
+    async fn persona_for(&self, conversation_id: &str) -> Option<String> {
+        match self.vdb.as_ref()?.get(&persona_id(conversation_id)).await {
+            Ok(entry) => entry.map(|e| e.content),
+            Err(e) => {
+                warn!(error = %e, "Failed to load persona override");
+                None
+            }
+        }
+    }
+
+    /// Stores or, when `persona` is empty, clears the persona override for
+    /// `conversation_id`. Returns the acknowledgement to reply with.
+    async fn set_persona(&self, conversation_id: &str, persona: &str) -> Result<String> {
+        let Some(vdb) = self.memory() else {
+            return Ok("I can't keep a persona for this thread with my memory off.".to_string());
+        };
+        let id = persona_id(conversation_id);
+        if persona.is_empty() {
+            vdb.delete(&id).await?;
+            info!(conversation_id, "Cleared persona override");
+            return Ok("Back to my usual self in this thread.".to_string());
+        }
+        if persona.chars().count() > MAX_PERSONA_CHARS {
+            return Ok(format!(
+                "That persona is too long, please keep it under {} characters.",
+                MAX_PERSONA_CHARS
+            ));
+        }
+
+        let Some(embedding) = self.embed(vec![persona.to_string()])?.into_iter().next() else {
+            return Err(anyhow::anyhow!("embedder returned no vector for persona"));
+        };
+        vdb.put(MemoryEntry {
+            id,
+            content: persona.to_string(),
+            embedding,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs() as i64,
+            tags: vec!["persona".to_string()],
+            role: "user".to_string(),
+            entry_type: "persona".to_string(),
+            conversation_id: conversation_id.to_string(),
+        })
+        .await?;
+        info!(conversation_id, "Set persona override");
+        Ok("Got it, I'll use that persona in this thread.".to_string())
+    }
+
+    fn build_reply_ref(
+        &self,
+        reply: Option<Object<ReplyRefData>>,
+        rcid: Cid,
+        msg_did: String,
+        collection: String,
+        rkey: String,
+    ) -> Object<ReplyRefData> {
+        if let Some(mut reply) = reply {
+            reply.parent = MainData {
+                cid: rcid,
+                uri: format!("at://{}/{}/{}", msg_did, collection, rkey),
+            }
+            .into();
+            reply
+        } else {
+            ReplyRefData {
+                parent: MainData {
+                    cid: rcid.clone(),
+                    uri: format!("at://{}/{}/{}", msg_did, collection, rkey),
+                }
+                .into(),
+                root: MainData {
+                    cid: rcid,
+                    uri: format!("at://{}/{}/{}", msg_did, collection, rkey),
+                }
+                .into(),
+            }
+            .into()
+        }
+    }
+
+    pub fn assemble_post_message(
+        &self,
+        post: Object<atrium_api::app::bsky::feed::defs::PostViewData>,
+    ) -> Result<String> {
+        let author = post
+            .author
+            .display_name
+            .clone()
+            .map(|e| format!("{} ({})", e, post.author.handle.as_str()))
+            .unwrap_or_else(|| post.author.handle.as_str().to_owned());
+        let record_data =
+            atrium_api::app::bsky::feed::post::RecordData::try_from_unknown(post.record.clone())?;
+
+        Ok(format!("{}: {}", author, record_data.text))
+    }
+
+    /// Extracts basic post data for JSON serialization from a BlueSky post
+    ///
+    /// This function converts a BlueSky post object into our simplified PostData
+    /// structure that can be easily serialized to JSON.
+    pub fn extract_post_data(
+        &self,
+        post: Object<atrium_api::app::bsky::feed::defs::PostViewData>,
+    ) -> Result<PostData> {
+        let author = post
+            .author
+            .display_name
+            .clone()
+            .map(|e| format!("{} ({})", e, post.author.handle.as_str()))
+            .unwrap_or_else(|| post.author.handle.as_str().to_owned());
+        let record_data =
+            atrium_api::app::bsky::feed::post::RecordData::try_from_unknown(post.record.clone())?;
+
+        // Extract embed data if present; the record only links a quoted post, its
+        // text comes with the view
+        let mut embed = self.extract_post_embed(&record_data);
+        match &mut embed {
+            Some(PostEmbed::Record(record))
+            | Some(PostEmbed::RecordWithMedia(PostEmbedRecordWithMedia { record, .. })) => {
+                record.text = quoted_post_text(&post.embed);
+            }
+            _ => {}
+        }
+
+        Ok(PostData {
+            author,
+            text: record_data.text,
+            uri: post.uri.to_string(),
+            author_did: post.author.did.to_string(),
+            indexed_at: Some(post.indexed_at.as_str().to_owned()),
+            embed,
+        })
+    }
+
+    /// Builds PostData straight from a firehose record, for when the post view can't be fetched
+    ///
+    /// Without the post view there is no display name or handle, so the author is the DID.
+    fn post_data_from_record(
+        &self,
+        record_data: &atrium_api::app::bsky::feed::post::RecordData,
+        author_did: &str,
+        uri: &str,
+    ) -> PostData {
+        PostData {
+            author: author_did.to_string(),
+            text: record_data.text.clone(),
+            uri: uri.to_string(),
+            author_did: author_did.to_string(),
+            indexed_at: Some(record_data.created_at.as_str().to_owned()),
+            embed: self.extract_post_embed(record_data),
+        }
+    }
+
+    /// Extract embed data from a BlueSky post
+    fn extract_post_embed(
+        &self,
+        record_data: &atrium_api::app::bsky::feed::post::RecordData,
+    ) -> Option<PostEmbed> {
+        if let Some(embed) = &record_data.embed {
+            match embed {
+                atrium_api::types::Union::Refs(e) => match e {
+                    atrium_api::app::bsky::feed::post::RecordEmbedRefs::AppBskyEmbedImagesMain(object) => {
+                        let images = object
+                            .images
+                            .iter()
+                            .map(|img| PostEmbedImage {
+                                image: get_blob_ref(&img.image).unwrap_or("".to_string()),
+                                alt: Some(img.alt.clone()),
+                            })
+                            .collect();
+
+                        Some(PostEmbed::Images(PostEmbedImages { images }))
+                    },
+                    atrium_api::app::bsky::feed::post::RecordEmbedRefs::AppBskyEmbedVideoMain(object) => {
+                        Some(PostEmbed::Video(PostEmbedVideo {
+                            video: get_blob_ref(&object.video).unwrap_or("".to_string()),
+                            duration: None, // API doesn't provide duration directly
+                        }))
+                    },
+                    atrium_api::app::bsky::feed::post::RecordEmbedRefs::AppBskyEmbedExternalMain(object) => {
+                        Some(PostEmbed::External(PostEmbedExternal {
+                            uri: object.external.uri.to_string(),
+                            title: Some(object.external.title.clone()),
+                            description: Some(object.external.description.clone()),
+                        }))
+                    },
+                    atrium_api::app::bsky::feed::post::RecordEmbedRefs::AppBskyEmbedRecordMain(object) => {
+                        Some(PostEmbed::Record(PostEmbedRecord {
+                            record: object.record.uri.to_string(),
+                            title: None, // Record embeds don't have titles in the API
+                            text: None,
+                        }))
+                    },
+                    atrium_api::app::bsky::feed::post::RecordEmbedRefs::AppBskyEmbedRecordWithMediaMain(object) => {
+                        let record = PostEmbedRecord {
+                            record: object.record.record.uri.to_string(),
+                            title: None,
+                            text: None,
+                        };
+
+                        let media = match &object.media {
+                            atrium_api::types::Union::Refs(media_ref) => match media_ref {
+                                atrium_api::app::bsky::embed::record_with_media::MainMediaRefs::AppBskyEmbedImagesMain(images_obj) => {
+                                    let images = images_obj
+                                        .images
+                                        .iter()
+                                        .map(|img| PostEmbedImage {
+                                            image: get_blob_ref(&img.image).unwrap_or("".to_string()),
+                                            alt: Some(img.alt.clone()),
+                                        })
+                                        .collect();
+
+                                    vec![PostEmbedMedia::Images(PostEmbedImages { images })]
+                                },
+                                atrium_api::app::bsky::embed::record_with_media::MainMediaRefs::AppBskyEmbedVideoMain(video_obj) => {
+                                    vec![PostEmbedMedia::Video(PostEmbedVideo {
+                                        video: get_blob_ref(&video_obj.video).unwrap_or("".to_string()),
+                                        duration: None, // API doesn't provide duration directly
+                                    })]
+                                },
+                                _ => vec![],
+                            },
+                            _ => vec![],
+                        };
+
+                        Some(PostEmbed::RecordWithMedia(PostEmbedRecordWithMedia {
+                            record,
+                            media,
+                        }))
+                    },
+                },
+                atrium_api::types::Union::Unknown(_) => None,
+            }
+        } else {
+            None
+        }
+    }
+
+    // New helper function to collect posts by traversing up the parent chain
+    fn collect_parents_recursive(
+        &self,
+        current_thread_view: Box<Object<ThreadViewPostData>>,
+        posts: &mut Vec<Object<PostViewData>>,
+    ) -> Result<()> {
+        // Add the current post to the list
+        posts.push(current_thread_view.post.clone());
+
+        // Check if there's a parent and if it's a ThreadViewPost
+        // Stop if the parent is a ThreadViewNotFound, blocked, or an unknown type
+        if let Some(atrium_api::types::Union::Refs(
+            atrium_api::app::bsky::feed::defs::ThreadViewPostParentRefs::ThreadViewPost(
+                parent_object,
+            ),
+        )) = current_thread_view.parent.clone()
+        {
+            // Recursively call for the parent
+            self.collect_parents_recursive(parent_object, posts)?;
+        }
+        Ok(())
+    }
+
+    /// Extracts a thread as a collection of structured JSON-serializable PostData objects
+    ///
+    /// This function fetches a thread by its URI and returns a vector of PostData objects
+    /// representing each post in the thread in chronological order (oldest to newest).
+    pub async fn atp_thread_to_json(&self, uri: &str) -> Result<Vec<PostData>> {
+        let mut all_posts: Vec<Object<atrium_api::app::bsky::feed::defs::PostViewData>> =
+            Vec::new();
+
+        let thread_result = self
+            .agent
+            .api
+            .app
+            .bsky
+            .feed
+            .get_post_thread(
+                get_post_thread::ParametersData {
+                    uri: uri.to_string(),
+                    // Only the chain of parents is read, so no replies are fetched at all
+                    depth: Some(LimitedU16::MIN),
+                    parent_height: LimitedU16::try_from(THREAD_PARENT_HEIGHT).ok(),
+                }
+                .into(),
+            )
+            .await?;
+
+        match &thread_result.thread {
+            // Match on a reference
+            atrium_api::types::Union::Refs(r) => match r {
+                get_post_thread::OutputThreadRefs::AppBskyFeedDefsThreadViewPost(object) => {
+                    // Start collecting from the latest post (which is the root of this fetched thread)
+                    self.collect_parents_recursive(object.clone(), &mut all_posts)?;
+                }
+                _ => return Err(anyhow::anyhow!("Unexpected thread type")),
+            },
+            _ => return Err(anyhow::anyhow!("Unexpected ref type")),
+        };
+
+        // The posts were collected from child to parent (latest to oldest),
+        // so reverse to get chronological order (oldest to latest).
+        all_posts.reverse();
+
+        // Convert sorted posts to PostData
+        let post_data: Vec<PostData> = all_posts
+            .into_iter()
+            .filter_map(|post| self.extract_post_data(post).ok())
+            .collect();
+
+        Ok(post_data)
+    }
+
+    /// Converts a collection of PostData objects to a JSON string
+    ///
+    /// This function serializes a vector of PostData objects into a JSON string,
+    /// which can be used for storage, transmission, or display purposes.
+    pub fn post_data_to_json_string(&self, post_data: &Vec<PostData>) -> Result<String> {
+        serde_json::to_string(post_data)
+            .map_err(|e| anyhow::anyhow!("Failed to serialize post data: {}", e))
+    }
+
+    /// Converts PostData objects to ChatMessage objects for LLM interaction
+    ///
+    /// This function transforms a vector of PostData objects into ChatMessage objects
+    /// with the post content formatted as user messages for LLM processing.
+    /// Convert post data to chat messages
+    pub fn json_to_chatmessages(&self, post_data: Vec<PostData>) -> Vec<ChatMessage> {
+        let now = time::OffsetDateTime::now_utc();
+        post_data
+            .into_iter()
+            .map(|post| {
+                let mut message = match post
+                    .indexed_at
+                    .as_deref()
+                    .and_then(|at| self.timestamps.format(at, now))
+                {
+                    Some(stamp) => format!("[{}] {}: {}", stamp, post.author, post.text),
+                    None => format!("{}: {}", post.author, post.text),
+                };
+
+                // Add embed information if present
+                if let Some(embed) = post.embed {
+                    match embed {
+                        PostEmbed::Images(images) => {
+                            message.push_str("\n[Images: ");
+                            for (i, img) in images.images.iter().enumerate() {
+                                if i > 0 {
+                                    message.push_str(", ");
+                                }
+                                if let Some(alt) = &img.alt {
+                                    message.push_str(&format!("\"{}\"", alt));
+                                } else {
+                                    message.push_str("image");
+                                }
+                            }
+                            message.push(']');
+                        }
+                        PostEmbed::External(external) => {
+                            message.push_str(&format!("\n[External link: {}]", external.uri));
+                            if let Some(title) = external.title {
+                                message.push_str(&format!(" - \"{}\"", title));
+                            }
+                        }
+                        PostEmbed::Video(_) => {
+                            message.push_str("\n[Video]");
+                        }
+                        PostEmbed::Record(record) => {
+                            message.push_str(&format!("\n[Quoted post: {}]", record.record));
+                        }
+                        PostEmbed::RecordWithMedia(record_with_media) => {
+                            message.push_str(&format!(
+                                "\n[Quoted post with media: {}]",
+                                record_with_media.record.record
+                            ));
+                        }
+                    }
+                }
+
+                ChatMessage::user(message)
+            })
+            .collect()
+    }
+
+    /// Converts PostData objects to a single ChatMessage containing the JSON string
+    ///
+    /// Instead of creating individual ChatMessages for each post, this function
+    /// serializes the entire collection of posts into a single JSON string and
+    /// wraps it in a single ChatMessage. This is useful when you want to pass
+    /// the structured data to an LLM that can parse JSON.
+    pub fn json_to_stringified_chatmessages(
+        &self,
+        post_data: Vec<PostData>,
+    ) -> Result<Vec<ChatMessage>> {
+        let json_string = self.post_data_to_json_string(&post_data)?;
+        Ok(vec![ChatMessage::user(json_string)])
+    }
+
+    /// Create a MemoryEntry from a PostData object
+    ///
+    /// This function converts a PostData object into a MemoryEntry that can be
+    /// stored in the vector database. It creates a unique ID based on the post URI
+    /// and stores [`PostData::embedding_text`], which `embedding` must be computed from.
+    pub fn create_memory_entry_from_post(
+        &self,
+        post_data: &PostData,
+        embedding: Vec<f32>,
+    ) -> MemoryEntry {
+        // Create a UUID based on the post URI
+        let entry_id =
+            uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_DNS, post_data.uri.as_bytes()).to_string();
+
+        // Create a conversation ID from the author DID
+        let conv_id =
+            uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_DNS, post_data.author_did.as_bytes())
+                .to_string();
+
+        // Add additional tags based on embed content
+        let mut tags = vec!["bluesky_post".to_string()];
+
+        if let Some(embed) = &post_data.embed {
+            match embed {
+                PostEmbed::Images(_) => {
+                    tags.push("has_images".to_string());
+                }
+                PostEmbed::External(_) => {
+                    tags.push("has_external_link".to_string());
+                }
+                PostEmbed::Video(_) => {
+                    tags.push("has_video".to_string());
+                }
+                PostEmbed::Record(_) => {
+                    tags.push("has_quote".to_string());
+                }
+                PostEmbed::RecordWithMedia(_) => {
+                    tags.push("has_quote_with_media".to_string());
+                }
+            }
+        }
+
+        MemoryEntry {
+            id: entry_id,
+            content: post_data.embedding_text(&self.embedding_fields),
+            tags,
+            embedding,
+            conversation_id: conv_id,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs() as i64,
+            role: "user".to_string(),
+            entry_type: "bluesky_post".to_string(),
+        }
+    }
+
+    /// Fetches a thread and converts it directly to ChatMessages
+    ///
+    /// This is a convenience method that combines atp_thread_to_json() and
+    /// json_to_chatmessages() to directly convert a thread to ChatMessage objects.
+    /// This maintains backward compatibility with existing code.
+    pub async fn atp_thread_to_chatmessages(&self, uri: &str) -> Result<Vec<ChatMessage>> {
+        let post_data = self.atp_thread_to_json(uri).await?;
+        Ok(self.json_to_chatmessages(post_data))
+    }
+}
+
+/// A cool ingestor implementation.
+#[async_trait]
+impl LexiconIngestor for PostListener {
+    async fn ingest(&self, message: Event<Value>) -> anyhow::Result<()> {
+        // set up timer
+        let timer = Instant::now();
+
+        let outcome = self
+            .process(message)
+            .await
+            .unwrap_or_else(IngestOutcome::Error);
+
+        INGEST_LATENCY.record(timer.elapsed());
+
+        match outcome {
+            IngestOutcome::Replied => {
+                POSTS_INGESTED.increment(1);
+                REPLIES_POSTED.increment(1);
+                Ok(())
+            }
+            IngestOutcome::Skipped(reason) => {
+                POSTS_INGESTED.increment(1);
+                metrics::counter!("posts_skipped_total", "reason" => reason.as_str()).increment(1);
+                Ok(())
+            }
+            IngestOutcome::Error(e) => {
+                INGEST_ERRORS.increment(1);
+                error!(error = %e, "Failed to ingest post");
+                Err(e)
+            }
+        }
+    }
+}
+
+/// What [`PostListener`] did with a post.
+#[derive(Debug)]
+pub enum IngestOutcome {
+    /// A reply was posted.
+    Replied,
+    /// The post was deliberately not replied to.
+    Skipped(SkipReason),
+    Error(anyhow::Error),
+}
+
+/// Why a post was not replied to, used as the `reason` label of `posts_skipped_total`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The event wasn't a post creation.
+    NotAPost,
+    /// The post doesn't mention, reply to or quote the bot.
+    NotTriggered,
+    NotAllowlisted,
+    /// The thread had no text to embed.
+    NoText,
+    NoEmbedding,
+    /// The model chose not to reply.
+    EmptyResponse,
+    /// The reply tripped the output wordlist.
+    Filtered,
+    /// The post only has media and no text of its own.
+    MediaOnly,
+    /// The post quotes another without adding any text.
+    BareQuote,
+    /// The post is older than [`ListenerConfig::max_post_age`].
+    TooOld,
+    /// A reply was generated but only logged, see [`ListenerConfig::dry_run`].
+    DryRun,
+    /// The thread is shorter than [`ListenerConfig::min_thread_posts`] and asks nothing.
+    ThinThread,
+}
+
+impl SkipReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SkipReason::NotAPost => "not_a_post",
+            SkipReason::NotTriggered => "not_triggered",
+            SkipReason::NotAllowlisted => "not_allowlisted",
+            SkipReason::NoText => "no_text",
+            SkipReason::NoEmbedding => "no_embedding",
+            SkipReason::EmptyResponse => "empty_response",
+            SkipReason::Filtered => "filtered",
+            SkipReason::MediaOnly => "media_only",
+            SkipReason::BareQuote => "bare_quote",
+            SkipReason::TooOld => "too_old",
+            SkipReason::DryRun => "dry_run",
+            SkipReason::ThinThread => "thin_thread",
+        }
+    }
+}
+
+impl PostListener {
+    /// Decides whether to reply to a post, replies, and stores the exchange.
+    pub async fn process(&self, message: Event<Value>) -> anyhow::Result<IngestOutcome> {
+        self.handle_post(message, false).await
+    }
+
+    /// Replies to the post at `uri` as if it had just come in from the firehose, e.g. to
+    /// catch up on a post missed while offline or to reproduce a bad reply. The post is
+    /// replied to even if it doesn't address the bot or would hit a stop condition.
+    pub async fn reply_to_uri(&self, uri: &str) -> anyhow::Result<IngestOutcome> {
+        let output = self
+            .agent
+            .api
+            .app
+            .bsky
+            .feed
+            .get_posts(
+                get_posts::ParametersData {
+                    uris: vec![uri.to_string()],
+                }
+                .into(),
+            )
+            .await?;
+        let Some(post) = output.data.posts.into_iter().next() else {
+            return Err(anyhow::anyhow!("Post not found: {}", uri));
+        };
+        let Some((collection, rkey)) = post
+            .uri
+            .strip_prefix("at://")
+            .and_then(|path| path.split_once('/'))
+            .and_then(|(_, path)| path.split_once('/'))
+        else {
+            return Err(anyhow::anyhow!("Not a post URI: {}", post.uri));
+        };
+
+        let message = Event {
+            did: post.author.did.to_string(),
+            time_us: None,
+            kind: Kind::Commit,
+            commit: Some(Commit {
+                rev: String::new(),
+                operation: Operation::Create,
+                collection: collection.to_string(),
+                rkey: rkey.to_string(),
+                record: Some(serde_json::to_value(&post.record)?),
+                cid: Some(post.cid.as_ref().to_string()),
+            }),
+            identity: None,
+        };
+        self.handle_post(message, true).await
+    }
+
+    /// The reply pipeline behind [`PostListener::process`] and
+    /// [`PostListener::reply_to_uri`]. A `manual` post skips the checks that decide
+    /// whether the bot should answer it at all.
+    async fn handle_post(&self, message: Event<Value>, manual: bool) -> Result<IngestOutcome> {
+        let Some(Commit {
+            record: Some(record),
+            cid: Some(_),
+            rkey,
+            collection,
+            ..
+        }) = &message.commit
+        else {
+            return Ok(IngestOutcome::Skipped(SkipReason::NotAPost));
+        };
+
+        // Every trigger names the bot's DID somewhere in the record, so most of the
+        // firehose can be dropped before it's deserialized
+        if !manual && !contains_str(record, &self.did_string) {
+            return Ok(IngestOutcome::Skipped(SkipReason::NotTriggered));
+        }
+
+        // Posts are handled concurrently, so everything logged for this one, down to
+        // the LLM and tool calls, carries the same id
+        let span = tracing::info_span!(
+            "PostListener::ingest",
+            correlation_id = %uuid::Uuid::new_v4().simple(),
+            aturl = format!("at://{}/{}/{}", message.did, collection, rkey),
+        );
+        self.reply_to_post(message, manual).instrument(span).await
+    }
+
+    /// Everything in [`PostListener::handle_post`] after the cheap trigger check, run
+    /// inside the post's ingest span.
+    async fn reply_to_post(&self, message: Event<Value>, manual: bool) -> Result<IngestOutcome> {
+        if let Some(Commit {
+            record: Some(record),
+            cid: Some(cid),
+            rkey,
+            collection,
+            ..
+        }) = message.commit
+        {
+            let riposte =
+                serde_json::from_value::<atrium_api::app::bsky::feed::post::RecordData>(record)?;
+
+            let aturi = format!("at://{}/{}/{}", message.did, collection, rkey);
+
+            trace!("Processing post");
+
+            trace!("recieved {}", riposte.text);
+
+            // is user mentioning me or allowlisted
+            if !manual {
+                if !self.is_me(&riposte) {
+                    return Ok(IngestOutcome::Skipped(SkipReason::NotTriggered));
+                }
+                if !self.is_allowlisted(&message.did) {
+                    return Ok(IngestOutcome::Skipped(SkipReason::NotAllowlisted));
+                }
+                if let Some(reason) = self.stop_condition(&riposte) {
+                    debug!(reason = reason.as_str(), "post hit a stop condition");
+                    return Ok(IngestOutcome::Skipped(reason));
+                }
+            }
+
+            // get the cid
+            let rcid = match Cid::from_str(&cid) {
+                Ok(r) => r,
+                Err(e) => return Err(anyhow::anyhow!(e)),
+            };
+
+            let reply = self.build_reply_ref(
+                riposte.reply.clone(),
+                rcid,
+                message.did.clone(),
+                collection,
+                rkey,
+            );
+
+            // Get the URI from the reply for later use
+            let root_uri = reply.root.uri.clone();
+            let convid = conversation_id(&root_uri);
+
+            // The persona becomes a system message, so only an explicit allowlist may set it
+            if let Some(persona) = persona_command(&riposte.text) {
+                if !self.may_set_persona(&message.did) {
+                    info!("ignoring persona command from a user not on the allowlist");
+                    return Ok(IngestOutcome::Skipped(SkipReason::NotAllowlisted));
+                }
+                if self.dry_run {
+                    info!(persona, "[dry run] would change this thread's persona");
+                    return Ok(IngestOutcome::Skipped(SkipReason::DryRun));
+                }
+                let ack = self.set_persona(&convid, persona).await?;
+                create_post(
+                    &self.agent,
+                    atrium_api::app::bsky::feed::post::RecordData {
+                        created_at: Datetime::now(),
+                        embed: None,
+                        entities: None,
+                        facets: None,
+                        labels: None,
+                        langs: Some(vec![self.lang.clone()]),
+                        reply: Some(reply),
+                        tags: None,
+                        text: ack,
+                    },
+                )
+                .await?;
+                return Ok(IngestOutcome::Replied);
+            }
+
+            // When someone quotes the bot, the reply can carry the quoted post along
+            let quoted = self
+                .quoted_post_of_mine(&riposte)
+                .filter(|_| self.embed_quoted && self.trigger.quote)
+                .cloned();
+
+            trace!("replying...");
+
+            // A follow-up in a thread fetched moments ago builds on that fetch
+            let parent_uri = riposte.reply.as_ref().map(|r| r.parent.uri.clone());
+            let cached = parent_uri
+                .as_deref()
+                .and_then(|parent| self.threads.chain_to(&root_uri, parent));
+
+            // Extract thread as JSON data, falling back to just this post if the
+            // thread can't be fetched so the user still gets a reply
+            let post_data = if let Some(mut chain) = cached {
+                debug!(posts = chain.len(), "reusing recently fetched thread");
+                chain.push(self.post_data_from_record(&riposte, &message.did, &aturi));
+                self.threads.update(&root_uri, chain.clone());
+                chain
+            } else {
+                match timed(
+                    "thread_fetch",
+                    &THREAD_FETCH_LATENCY,
+                    self.atp_thread_to_json(&aturi),
+                )
+                .await
+                {
+                    Ok(post_data) => {
+                        self.threads.insert(&root_uri, post_data.clone());
+                        post_data
+                    }
+                    Err(e) => {
+                        warn!(error = %e, "Failed to fetch thread, replying to the post alone");
+                        vec![self.post_data_from_record(&riposte, &message.did, &aturi)]
+                    }
+                }
+            };
+
+            // A bare mention with nothing around it tends to get a generic reply,
+            // unless it at least asks something
+            if !manual && post_data.len() < self.min_thread_posts && !riposte.text.contains('?') {
+                debug!(
+                    posts = post_data.len(),
+                    min = self.min_thread_posts,
+                    "thread too thin, not replying"
+                );
+                return Ok(IngestOutcome::Skipped(SkipReason::ThinThread));
+            }
+
+            // Convert to chat messages for LLM processing
+            let thread = self.json_to_chatmessages(post_data.clone());
+
+            trace!("{:?}", &thread);
+            trace!("JSON post data: {:?}", &post_data);
+
+            // Example of getting the stringified JSON version
+            if let Ok(json_string) = self.post_data_to_json_string(&post_data) {
+                trace!("Stringified JSON: {}", json_string);
+            }
+
+            // Thread texts overlap between retrieval and the stored exchange
+            let mut embedded = HashMap::new();
+
+            // Each message carries its author, so whether a post has text comes from its data
+            let texts: Vec<String> = thread
+                .iter()
+                .zip(&post_data)
+                .filter(|(_, post)| !post.text.trim().is_empty())
+                .filter_map(|(message, _)| message.content.text_as_str().map(|s| s.to_string()))
+                .collect();
+
+            if texts.is_empty() {
+                debug!("thread has no text to embed, not replying");
+                return Ok(IngestOutcome::Skipped(SkipReason::NoText));
+            }
+
+            // search db for similar posts, unless memory is off or down with the embedder
+            let mut similar_posts = if let Some(vdb) = self.memory() {
+                let vecs = self.embed_unique(texts, &mut embedded)?;
+                let Some(query_vec) = vecs.last().cloned() else {
+                    debug!("embedder returned no vectors, not replying");
+                    return Ok(IngestOutcome::Skipped(SkipReason::NoEmbedding));
+                };
+
+                timed(
+                    "vdb_search",
+                    &VDB_SEARCH_LATENCY,
+                    vdb.get_similar_any(
+                        None,
+                        query_vec,
+                        self.retrieval_tags.clone(),
+                        self.memory_top_k,
+                        self.memory_min_score,
+                    ),
+                )
+                .await?
+            } else {
+                Vec::new()
+            };
+            debug!("similar posts: {:?}", similar_posts);
+
+            // Deduplicate by ID
+            similar_posts.dedup_by_key(|p| p.id.clone());
+
+            // One message per memory, so each keeps its own provenance
+            let now = time::OffsetDateTime::now_utc();
+            let memory_messages: Vec<ChatMessage> = similar_posts
+                .iter()
+                .map(|entry| memory_message(entry, now))
+                .collect();
+
+            debug!("search results: {:?}", &memory_messages);
+
+            // Memories go separately, placed by the LLM service's prompt layout
+            let memories = (!memory_messages.is_empty()).then_some(memory_messages.as_slice());
+
+            // Create initial message array to send to the LLM
+            let mut messages = thread.clone();
+            if let Some(persona) = self.persona_for(&convid).await {
+                debug!("using persona override for this thread");
+                messages.insert(
+                    0,
+                    ChatMessage::system(format!(
+                        "In this thread, take on the following persona: {}",
+                        persona
+                    )),
+                );
+            }
+
+            // Questions, banter etc. can each get their own prompt
+            let prompt = self.intents.route(&riposte.text);
+            if let Some(prompt) = prompt {
+                debug!(prompt, "routed to intent prompt");
+                metrics::counter!("intent_routed_total", "prompt" => prompt.to_string())
+                    .increment(1);
+            }
+
+            // Get initial response from LLM
+            let initial_resp = timed(
+                "llm",
+                &LLM_LATENCY,
+                self.generate(&messages, memories, prompt),
+            )
+            .await
+            .inspect(|x| trace!("original: {x}"))?;
+
+            // Process any tool calls in the response
+            info!("Processing tool calls in LLM response...");
+
+            // Tool call loop to allow chaining of multiple tool calls
+            let mut response_accum = initial_resp.clone();
+            let mut loop_guard = ToolLoopGuard::from_env();
+            // filled by the attach_image tool
+            let reply_images: Arc<std::sync::Mutex<Vec<ReplyImage>>> = Default::default();
+            // every call and its result, stored with the reply for auditing
+            let mut tool_trace: Vec<Value> = Vec::new();
+
+            loop {
+                let tool_calls = parse_tool_calls(&response_accum);
+                if !tool_calls.is_empty() {
+                    // Check for repeated tool calls
+                    if !loop_guard.check(&tool_calls) {
+                        let reason = if loop_guard.hit_iteration_cap() {
+                            warn!(
+                                "Tool call iteration cap reached, breaking out of the tool loop."
+                            );
+                            "iterations"
+                        } else {
+                            debug!(
+                                "Too many repeated tool calls, breaking to avoid infinite loop."
+                            );
+                            "repeats"
+                        };
+                        metrics::counter!("tool_loop_capped_total", "reason" => reason)
+                            .increment(1);
+                        break;
+                    }
+
+                    // Execute all tool calls in order
+                    debug!("Executing {} tool calls", tool_calls.len());
+                    // Tools like reminders reply to this post later, and remembered
+                    // facts belong to its author
+                    let tool_ctx = ToolContext {
+                        agent: Some(&self.agent),
+                        dry_run: self.dry_run,
+                    };
+                    let tool_results = POSTER_DID
+                        .scope(
+                            message.did.clone(),
+                            REPLY_TARGET.scope(
+                                reply.clone(),
+                                REPLY_IMAGES.scope(
+                                    reply_images.clone(),
+                                    execute_tool_calls(&tool_calls, &self.tools, &tool_ctx),
+                                ),
+                            ),
+                        )
+                        .await;
+
+                    for (call, (tool_name, result)) in tool_calls.iter().zip(&tool_results) {
+                        let result = match result {
+                            Ok(value) => value.to_string(),
+                            Err(e) => format!("Error: {}", e),
+                        };
+                        tool_trace.push(serde_json::json!({
+                            "tool": tool_name,
+                            "args": call.tool_args,
+                            "result": truncate_reply(&result, MAX_TRACE_RESULT_CHARS),
+                        }));
+                    }
+
+                    // Add the assistant response to the conversation
+                    messages.push(ChatMessage::assistant(response_accum.clone()));
+
+                    // Add each tool response to the conversation
+                    for (tool_name, result) in &tool_results {
+                        match result {
+                            Ok(tool_result) => {
+                                debug!("Tool '{}' returned: {}", tool_name, tool_result);
+                                messages.push(
+                                    ToolResponse::new(tool_name.clone(), tool_result.to_string())
+                                        .into(),
+                                );
+                            }
+                            Err(e) => {
+                                debug!("Tool '{}' error: {}", tool_name, e);
+                                messages.push(
+                                    ToolResponse::new(tool_name.clone(), format!("Error: {}", e))
+                                        .into(),
+                                );
+                            }
+                        }
+                    }
+
+                    // Stop if the model keeps calling tools that don't exist, or repeats
+                    // a call whose arguments were already rejected
+                    if !loop_guard.check_results(&tool_calls, &tool_results) {
+                        debug!(
+                            "Model keeps making failing tool calls, falling back to a plain answer."
+                        );
+                        metrics::counter!("tool_loop_capped_total", "reason" => "failing_calls")
+                            .increment(1);
+                        response_accum = strip_tool_calls(&response_accum);
+                        break;
+                    }
+
+                    // Get follow-up response
+                    let followup_resp = timed(
+                        "llm",
+                        &LLM_LATENCY,
+                        self.generate(&messages, memories, prompt),
+                    )
+                    .await?;
+
+                    // Prepare for next loop iteration
+                    response_accum = followup_resp;
+                    continue;
+                } else {
+                    // No tool call, we're done
+                    break;
+                }
+            }
+
+            TOOL_LOOP_ITERATIONS.record(loop_guard.iterations() as f64);
+
+            let final_resp = response_accum;
+
+            // remove <think> tag
+            let resp = final_resp
+                .split("</think>")
+                .collect::<Vec<&str>>()
+                .last()
+                .ok_or(anyhow::anyhow!("no response outputted?"))?
+                .trim()
+                .to_string();
+
+            // if response is empty, just return ok
+            if resp.trim().is_empty() {
+                debug!("aigis doesn't want to reply, so not replying");
+                return Ok(IngestOutcome::Skipped(SkipReason::EmptyResponse));
+            }
+
+            // don't post anything that trips the output wordlist
+            if sanitize_output(&resp).1 {
+                info!("reply contains filtered words, not replying");
+                return Ok(IngestOutcome::Skipped(SkipReason::Filtered));
+            }
+
+            // the prompt asks for the same limit, this catches replies that ignore it
+            let resp = truncate_reply(&resp, self.reply_max_chars);
+
+            let images = std::mem::take(&mut *reply_images.lock().unwrap());
+            if self.dry_run {
+                info!(reply = %resp, images = images.len(), "[dry run] would reply");
+                return Ok(IngestOutcome::Skipped(SkipReason::DryRun));
+            }
+            let embed = self.reply_embed(quoted, images).await?;
+            self.wait_before_reply().await;
+
+            let reply_record = atrium_api::app::bsky::feed::post::RecordData {
+                created_at: Datetime::now(),
+                embed,
+                entities: None,
+                facets: None,
+                labels: None,
+                langs: Some(resolve_reply_langs(&riposte.text, riposte.langs.clone())),
+                reply: Some(reply),
+                tags: None,
+                text: resp.trim().to_string(),
+            };
+            let reply_uri = create_post(&self.agent, reply_record.clone()).await?;
+            // so a quick answer to this reply finds it without refetching
+            self.threads.append(
+                &root_uri,
+                &aturi,
+                self.post_data_from_record(&reply_record, &self.did_string, &reply_uri),
+            );
+
+            let Some(vdb) = self.memory() else {
+                debug!("memory is off or the embedder is unavailable, not storing conversation");
+                return Ok(IngestOutcome::Replied);
+            };
+            let Some(post) = post_data.last() else {
+                debug!("thread has no posts, not storing conversation");
+                return Ok(IngestOutcome::Replied);
+            };
+
+            // the post and the reply are stored separately, linked by the thread root
+            let post_text = post.embedding_text(&self.embedding_fields);
+            let reply_text = resp.trim().to_string();
+            let mut vectors = self
+                .embed_unique(vec![post_text.clone(), reply_text.clone()], &mut embedded)?
+                .into_iter();
+            let (Some(post_vec), Some(reply_vec)) = (vectors.next(), vectors.next()) else {
+                debug!("embedder returned too few vectors, not storing conversation");
+                return Ok(IngestOutcome::Replied);
+            };
+
+            let reply_timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs() as i64;
+            // the post must sort before the reply, even if both land in the same second
+            let post_timestamp = post
+                .indexed_at
+                .as_deref()
+                .and_then(|at| {
+                    time::OffsetDateTime::parse(at, &time::format_description::well_known::Rfc3339)
+                        .ok()
+                })
+                .map(|at| at.unix_timestamp())
+                .unwrap_or(reply_timestamp)
+                .min(reply_timestamp - 1);
+
+            let mut memtries = vec![
+                MemoryEntry {
+                    id: uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_DNS, post.uri.as_bytes())
+                        .to_string(),
+                    content: post_text,
+                    tags: vec!["stm".to_string()],
+                    embedding: post_vec,
+                    conversation_id: convid.clone(),
+                    timestamp: post_timestamp,
+                    role: "user".to_string(),
+                    entry_type: "bluesky_post".to_string(),
+                },
+                MemoryEntry {
+                    id: uuid::Uuid::new_v5(
+                        &uuid::Uuid::NAMESPACE_DNS,
+                        format!("{}#reply", post.uri).as_bytes(),
+                    )
+                    .to_string(),
+                    content: reply_text,
+                    tags: vec!["stm".to_string()],
+                    embedding: reply_vec.clone(),
+                    conversation_id: convid.clone(),
+                    timestamp: reply_timestamp,
+                    role: "assistant".to_string(),
+                    entry_type: "reply".to_string(),
+                },
+            ];
+            // not tagged "stm", so it's never pulled into a prompt
+            if !tool_trace.is_empty() {
+                memtries.push(MemoryEntry {
+                    id: tool_trace_id(&post.uri),
+                    content: Value::Array(tool_trace).to_string(),
+                    tags: vec!["tool_trace".to_string()],
+                    embedding: reply_vec,
+                    conversation_id: convid,
+                    timestamp: reply_timestamp,
+                    role: "assistant".to_string(),
+                    entry_type: "tool_trace".to_string(),
+                });
+            }
+
+            // busy threads get the same posts processed again, so skip what's already stored
+            vdb.put_batch_if_absent(memtries).await?;
+            return Ok(IngestOutcome::Replied);
+        }
+        Ok(IngestOutcome::Skipped(SkipReason::NotAPost))
+    }
+}
+
+/// Byte offset in a streamed response where the answer starts, or `None` while the
+/// response is still in, or may yet open, an inline `<think>` block.
+fn answer_start(content: &str) -> Option<usize> {
+    const THINK_START: &str = "<think>";
+    const THINK_END: &str = "</think>";
+
+    let trimmed = content.trim_start();
+    if trimmed.is_empty() || THINK_START.starts_with(trimmed) {
+        return None;
+    }
+    if !trimmed.starts_with(THINK_START) {
+        return Some(0);
+    }
+    content.find(THINK_END).map(|end| end + THINK_END.len())
+}
+
+/// Text of the post quoted in a post view's embed, if the view includes it.
+fn quoted_post_text(
+    embed: &Option<atrium_api::types::Union<atrium_api::app::bsky::feed::defs::PostViewEmbedRefs>>,
+) -> Option<String> {
+    use atrium_api::app::bsky::embed::record::ViewRecordRefs;
+    use atrium_api::app::bsky::feed::defs::PostViewEmbedRefs;
+
+    let view = match embed {
+        Some(atrium_api::types::Union::Refs(PostViewEmbedRefs::AppBskyEmbedRecordView(view))) => {
+            &**view
+        }
+        Some(atrium_api::types::Union::Refs(
+            PostViewEmbedRefs::AppBskyEmbedRecordWithMediaView(view),
+        )) => &view.record,
+        _ => return None,
+    };
+    let atrium_api::types::Union::Refs(ViewRecordRefs::ViewRecord(quoted)) = &view.record else {
+        return None;
+    };
+    let record =
+        atrium_api::app::bsky::feed::post::RecordData::try_from_unknown(quoted.value.clone())
+            .ok()?;
+    Some(record.text).filter(|text| !text.trim().is_empty())
+}
+
+/// Whether any string in `value`, however deeply nested, contains `needle`.
+fn contains_str(value: &Value, needle: &str) -> bool {
+    match value {
+        Value::String(s) => s.contains(needle),
+        Value::Array(items) => items.iter().any(|item| contains_str(item, needle)),
+        Value::Object(fields) => fields.values().any(|field| contains_str(field, needle)),
+        _ => false,
+    }
+}
+
+/// Conversation ID the bot stores a thread's exchanges under, derived from the URI of
+/// the thread's root post.
+pub fn conversation_id(root_uri: &str) -> String {
+    uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_DNS, root_uri.as_bytes()).to_string()
+}
+
+/// ID of the memory entry holding the tool calls, as a JSON array of
+/// `{tool, args, result}`, that led to the reply to the post at `post_uri`.
+pub fn tool_trace_id(post_uri: &str) -> String {
+    uuid::Uuid::new_v5(
+        &uuid::Uuid::NAMESPACE_DNS,
+        format!("{}#tool_trace", post_uri).as_bytes(),
+    )
+    .to_string()
+}
+
+/// ID of the entry holding the persona override for a conversation.
+fn persona_id(conversation_id: &str) -> String {
+    uuid::Uuid::new_v5(
+        &uuid::Uuid::NAMESPACE_DNS,
+        format!("persona:{}", conversation_id).as_bytes(),
+    )
+    .to_string()
+}
+
+/// Returns what follows [`PERSONA_COMMAND`] when `text` is a persona command, after any
+/// leading @mentions. An empty result means the override should be cleared.
+fn persona_command(text: &str) -> Option<&str> {
+    let mut rest = text.trim_start();
+    while rest.starts_with('@') {
+        rest = rest
+            .split_once(char::is_whitespace)
+            .map(|(_, r)| r.trim_start())
+            .unwrap_or("");
+    }
+    let args = rest.strip_prefix(PERSONA_COMMAND)?;
+    // "/personality" isn't the command
+    if !args.is_empty() && !args.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some(args.trim())
+}
+
+/// Runs one ingest stage inside its own span and records its duration in `histogram`.
+async fn timed<T>(
+    stage: &'static str,
+    histogram: &metrics::Histogram,
+    fut: impl Future<Output = T>,
+) -> T {
+    let timer = Instant::now();
+    let output = fut.instrument(tracing::info_span!("stage", stage)).await;
+    let elapsed = timer.elapsed();
+    trace!("{} took {:?}", stage, elapsed);
+    histogram.record(elapsed);
+    output
+}
+
+/// Environment-driven settings for a [`PostListener`].
+#[derive(Debug, Clone)]
+pub struct ListenerConfig {
+    /// DIDs allowed to trigger replies. `None` allows everyone unless
+    /// `allowed_list_uri` is set.
+    pub allowlist: Option<Vec<String>>,
+    /// AT-URI of an `app.bsky.graph.list` whose members are also allowed.
+    pub allowed_list_uri: Option<String>,
+    /// How often the `allowed_list_uri` membership is refetched.
+    pub allowed_list_refresh: Duration,
+    pub timestamps: TimestampStyle,
+    pub trigger: TriggerMode,
+    /// How many similar memories to put in the prompt.
+    pub memory_top_k: usize,
+    /// Minimum similarity score for a memory to be used, so a larger `memory_top_k`
+    /// doesn't pull in unrelated entries.
+    pub memory_min_score: Option<f32>,
+    /// Memories carrying any of these tags are searched for replies.
+    pub retrieval_tags: Vec<String>,
+    /// Embed the quoted post in replies to quotes of the bot.
+    pub embed_quoted: bool,
+    /// Run the whole pipeline but log replies instead of posting them, and store
+    /// nothing in memory.
+    pub dry_run: bool,
+    /// Don't reply to posts with media but no text besides mentions.
+    pub skip_media_only: bool,
+    /// Don't reply to quotes of other posts that add no text besides mentions.
+    pub skip_bare_quotes: bool,
+    /// Don't reply to posts created longer ago than this, e.g. ones caught up on
+    /// after downtime.
+    pub max_post_age: Option<Duration>,
+    /// Replies longer than this many characters are cut short. Prompts can refer to it
+    /// as `{reply_max_chars}`.
+    pub reply_max_chars: usize,
+    /// Offer the `attach_image` tool, letting replies carry images.
+    pub image_replies: bool,
+    /// Only reply when the thread has at least this many posts, or the post asks a
+    /// question.
+    pub min_thread_posts: usize,
+    /// Which embed fields are added to the text posts are embedded and stored as.
+    pub embedding_fields: EmbeddingFields,
+    /// Random wait before each reply goes out, so replies don't arrive instantly.
+    pub reply_delay: Option<ReplyDelay>,
+    /// Alternative prompts and the rules choosing between them per post.
+    pub intents: IntentRouter,
+}
+
+impl Default for ListenerConfig {
+    fn default() -> Self {
+        Self {
+            allowlist: None,
+            allowed_list_uri: None,
+            allowed_list_refresh: DEFAULT_LIST_REFRESH_INTERVAL,
+            timestamps: TimestampStyle::default(),
+            trigger: TriggerMode::default(),
+            memory_top_k: DEFAULT_MEMORY_TOP_K,
+            memory_min_score: None,
+            retrieval_tags: default_retrieval_tags(),
+            embed_quoted: false,
+            dry_run: false,
+            skip_media_only: false,
+            skip_bare_quotes: false,
+            max_post_age: None,
+            reply_max_chars: BLUESKY_MAX_GRAPHEMES,
+            image_replies: false,
+            min_thread_posts: DEFAULT_MIN_THREAD_POSTS,
+            embedding_fields: EmbeddingFields::default(),
+            reply_delay: None,
+            intents: IntentRouter::default(),
+        }
+    }
+}
+
+impl ListenerConfig {
+    /// Reads `ALLOWED_USERS` (comma-separated DIDs), `ALLOWED_LIST_URI`,
+    /// `ALLOWED_LIST_REFRESH_SECS`, `THREAD_TIMESTAMPS`, `TRIGGER_MODE`, `MEMORY_TOP_K`,
+    /// `MEMORY_MIN_SCORE`, `RETRIEVAL_TAGS`, `REPLY_EMBED_QUOTE`, `DRY_RUN`,
+    /// `SKIP_MEDIA_ONLY`, `SKIP_BARE_QUOTES`, `MAX_POST_AGE_SECS`, `REPLY_MAX_CHARS`,
+    /// `REPLY_IMAGES` and `MIN_THREAD_POSTS`.
+    pub fn from_env() -> Self {
+        let allowlist = std::env::var("ALLOWED_USERS")
+            .ok()
+            .map(|users| {
+                users
+                    .split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect::<Vec<_>>()
+            })
+            .filter(|users| !users.is_empty());
+        let allowed_list_uri = std::env::var("ALLOWED_LIST_URI")
+            .ok()
+            .map(|uri| uri.trim().to_string())
+            .filter(|uri| !uri.is_empty());
+        let allowed_list_refresh = std::env::var("ALLOWED_LIST_REFRESH_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&secs| secs > 0)
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_LIST_REFRESH_INTERVAL);
+
+        let memory_top_k = std::env::var("MEMORY_TOP_K")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MEMORY_TOP_K);
+        let memory_min_score = std::env::var("MEMORY_MIN_SCORE")
+            .ok()
+            .and_then(|v| v.parse().ok());
+        let retrieval_tags = std::env::var("RETRIEVAL_TAGS")
+            .ok()
+            .map(|tags| {
+                tags.split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect::<Vec<_>>()
+            })
+            .filter(|tags| !tags.is_empty())
+            .unwrap_or_else(default_retrieval_tags);
+        let max_post_age = std::env::var("MAX_POST_AGE_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&secs| secs > 0)
+            .map(Duration::from_secs);
+        let min_thread_posts = std::env::var("MIN_THREAD_POSTS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MIN_THREAD_POSTS);
+
+        Self {
+            allowlist,
+            allowed_list_uri,
+            allowed_list_refresh,
+            timestamps: TimestampStyle::from_env(),
+            trigger: TriggerMode::from_env(),
+            memory_top_k,
+            memory_min_score,
+            retrieval_tags,
+            embed_quoted: env_flag("REPLY_EMBED_QUOTE").unwrap_or(false),
+            dry_run: env_flag("DRY_RUN").unwrap_or(false),
+            skip_media_only: env_flag("SKIP_MEDIA_ONLY").unwrap_or(false),
+            skip_bare_quotes: env_flag("SKIP_BARE_QUOTES")
+                .or_else(|| {
+                    let old = env_flag("SKIP_REPOSTS");
+                    if old.is_some() {
+                        warn!("SKIP_REPOSTS is deprecated, use SKIP_BARE_QUOTES");
+                    }
+                    old
+                })
+                .unwrap_or(false),
+            max_post_age,
+            reply_max_chars: reply_max_chars(),
+            image_replies: env_flag("REPLY_IMAGES").unwrap_or(false),
+            min_thread_posts,
+            embedding_fields: EmbeddingFields::from_env(),
+            reply_delay: ReplyDelay::from_env(),
+            intents: IntentRouter::from_env(),
+        }
+    }
+}
+
+/// Only the conversation history, the "stm" tag the bot stores exchanges under.
+fn default_retrieval_tags() -> Vec<String> {
+    vec!["stm".to_string()]
+}
+
+/// Which ways of addressing the bot make it reply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriggerMode {
+    /// @-mentions of the bot.
+    pub mention: bool,
+    /// Direct replies to the bot's posts.
+    pub reply: bool,
+    /// Quote posts of the bot's posts.
+    pub quote: bool,
+}
+
+impl Default for TriggerMode {
+    fn default() -> Self {
+        Self {
+            mention: true,
+            reply: true,
+            quote: true,
+        }
+    }
+}
+
+impl TriggerMode {
+    /// Reads `TRIGGER_MODE`, a comma-separated list of `mention`, `reply`, `quote` or `any`.
+    pub fn from_env() -> Self {
+        let Ok(value) = std::env::var("TRIGGER_MODE") else {
+            return Self::default();
+        };
+        let mut mode = Self {
+            mention: false,
+            reply: false,
+            quote: false,
+        };
+        for signal in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            match signal.to_ascii_lowercase().as_str() {
+                "mention" => mode.mention = true,
+                "reply" => mode.reply = true,
+                "quote" => mode.quote = true,
+                "any" => mode = Self::default(),
+                _ => warn!("Ignoring unknown TRIGGER_MODE '{}'", signal),
+            }
+        }
+        if !(mode.mention || mode.reply || mode.quote) {
+            warn!("TRIGGER_MODE '{}' enables nothing, using 'any'", value);
+            return Self::default();
+        }
+        mode
+    }
+}
+
+/// Range a reply's random delay is picked from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplyDelay {
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl ReplyDelay {
+    /// Reads `REPLY_DELAY_MIN` and `REPLY_DELAY_MAX` in seconds. Either one alone
+    /// gives a range from zero or a fixed delay; neither, or both zero, means no delay.
+    pub fn from_env() -> Option<Self> {
+        let secs = |var: &str| {
+            std::env::var(var)
+                .ok()
+                .and_then(|v| v.trim().parse::<f64>().ok())
+                .filter(|secs| secs.is_finite() && *secs >= 0.0)
+                .map(Duration::from_secs_f64)
+        };
+        let (min, max) = match (secs("REPLY_DELAY_MIN"), secs("REPLY_DELAY_MAX")) {
+            (None, None) => return None,
+            (Some(min), None) => (min, min),
+            (None, Some(max)) => (Duration::ZERO, max),
+            (Some(min), Some(max)) if min > max => {
+                warn!("REPLY_DELAY_MIN is above REPLY_DELAY_MAX, swapping them");
+                (max, min)
+            }
+            (Some(min), Some(max)) => (min, max),
+        };
+        (!max.is_zero()).then_some(Self { min, max })
+    }
+
+    /// Picks a delay within the range. Taking the RNG lets a seeded one make it
+    /// reproducible.
+    pub fn sample(&self, rng: &mut impl Rng) -> Duration {
+        rng.gen_range(self.min..=self.max)
+    }
+}
+
+/// How post timestamps are shown to the model in chat messages.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampStyle {
+    /// No timestamps, saving tokens.
+    #[default]
+    Off,
+    /// How long ago the post was made, e.g. "3 days ago".
+    Relative,
+    /// The RFC 3339 time the post was indexed.
+    Absolute,
+}
+
+impl TimestampStyle {
+    /// Reads `THREAD_TIMESTAMPS` (`off`, `relative` or `absolute`).
+    pub fn from_env() -> Self {
+        match std::env::var("THREAD_TIMESTAMPS") {
+            Ok(v) if v.eq_ignore_ascii_case("relative") => Self::Relative,
+            Ok(v) if v.eq_ignore_ascii_case("absolute") => Self::Absolute,
+            Ok(v) if !v.eq_ignore_ascii_case("off") && !v.is_empty() => {
+                warn!("Unknown THREAD_TIMESTAMPS '{}', timestamps disabled", v);
+                Self::Off
+            }
+            _ => Self::Off,
+        }
+    }
+
+    /// Formats an ISO-8601 `indexed_at` value, or `None` if disabled or unparseable.
+    fn format(self, indexed_at: &str, now: time::OffsetDateTime) -> Option<String> {
+        if self == Self::Off {
+            return None;
+        }
+        let at =
+            time::OffsetDateTime::parse(indexed_at, &time::format_description::well_known::Rfc3339)
+                .ok()?;
+        match self {
+            Self::Relative => Some(format_relative(now - at)),
+            _ => Some(indexed_at.to_string()),
+        }
+    }
+}
+
+/// Renders a retrieved memory as a system message tagged with who said it, how long
+/// ago and what kind of entry it is, so it isn't mistaken for the current post.
+fn memory_message(entry: &MemoryEntry, now: time::OffsetDateTime) -> ChatMessage {
+    let age = time::OffsetDateTime::from_unix_timestamp(entry.timestamp)
+        .map(|at| format_relative(now - at))
+        .unwrap_or_else(|_| "unknown time".to_string());
+    ChatMessage::system(format!(
+        "[memory | {} | {} | {}] {}",
+        entry.role, age, entry.entry_type, entry.content
+    ))
+}
+
+fn format_relative(elapsed: time::Duration) -> String {
+    let (amount, unit) = if elapsed.whole_days() > 0 {
+        (elapsed.whole_days(), "day")
+    } else if elapsed.whole_hours() > 0 {
+        (elapsed.whole_hours(), "hour")
+    } else if elapsed.whole_minutes() > 0 {
+        (elapsed.whole_minutes(), "minute")
+    } else {
+        return "just now".to_string();
+    };
+    let plural = if amount == 1 { "" } else { "s" };
+    format!("{} {}{} ago", amount, unit, plural)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockAiService;
+
+    const BOT_DID: &str = "did:plc:aigistestbot";
+    const USER_DID: &str = "did:plc:aigistestuser";
+    const CID: &str = "bafyreiclp443lavogvhj3d2ob2cxbfuscni2k5jk7bebjzg7khl3esabwq";
+    const CALC_CALL: &str = "<｜tool▁calls▁begin｜><｜tool▁call▁begin｜>function<｜tool▁sep｜>calc\n```json\n{\"expr\": \"1+1\"}\n```<｜tool▁call▁end｜><｜tool▁calls▁end｜>";
+
+    async fn listener(config: ListenerConfig, ai: Arc<MockAiService>) -> PostListener {
+        let reminders = ReminderStore::load(temp_path("reminders.json"))
+            .await
+            .unwrap();
+        listener_with_reminders(config, ai, Arc::new(reminders)).await
+    }
+
+    async fn listener_with_reminders(
+        config: ListenerConfig,
+        ai: Arc<MockAiService>,
+        reminders: Arc<ReminderStore>,
+    ) -> PostListener {
+        let agent = BskyAgent::builder().build().await.unwrap();
+        PostListener::new(
+            agent,
+            Did::new(BOT_DID.to_string()).unwrap(),
+            None,
+            reminders,
+            None,
+            config,
+        )
+        .with_ai_service(ai)
+    }
+
+    /// A path in the temp dir no other test uses.
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("aigis-test-{}-{}", uuid::Uuid::new_v4(), name))
+    }
+
+    /// Turns memory on without a model or Qdrant, so nothing may reach the store.
+    fn enable_memory(listener: &mut PostListener) {
+        listener.emb = Some(Arc::new(Embedder::stub(vec![1.0, 0.0])));
+        listener.vdb = Some(Arc::new(MemoryStore::unconnected("aigis-test").unwrap()));
+    }
+
+    fn post(uri: &str, did: &str, text: &str) -> PostData {
+        PostData {
+            author: did.to_string(),
+            text: text.to_string(),
+            uri: uri.to_string(),
+            author_did: did.to_string(),
+            indexed_at: None,
+            embed: None,
+        }
+    }
+
+    /// A reply to the bot's post `parent` in the thread at `root`.
+    fn reply_event(root: &str, parent: &str, text: &str) -> Event<Value> {
+        let record = serde_json::json!({
+            "$type": "app.bsky.feed.post",
+            "text": text,
+            "createdAt": "2026-01-01T00:00:00.000Z",
+            "reply": {
+                "root": { "uri": root, "cid": CID },
+                "parent": { "uri": parent, "cid": CID },
+            },
+        });
+        Event {
+            did: USER_DID.to_string(),
+            time_us: None,
+            kind: Kind::Commit,
+            commit: Some(Commit {
+                rev: String::new(),
+                operation: Operation::Create,
+                collection: "app.bsky.feed.post".to_string(),
+                rkey: "reply".to_string(),
+                record: Some(record),
+                cid: Some(CID.to_string()),
+            }),
+            identity: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn whitespace_only_thread_is_skipped() {
+        let ai = Arc::new(MockAiService::new());
+        let mut listener = listener(ListenerConfig::default(), ai.clone()).await;
+        enable_memory(&mut listener);
+        let root = format!("at://{}/app.bsky.feed.post/root", USER_DID);
+        let parent = format!("at://{}/app.bsky.feed.post/parent", BOT_DID);
+        // cached, so the thread isn't fetched from Bluesky
+        listener.threads.insert(
+            &root,
+            vec![post(&root, USER_DID, "  "), post(&parent, BOT_DID, "\n")],
+        );
+
+        let outcome = listener
+            .process(reply_event(&root, &parent, " \t "))
+            .await
+            .unwrap();
+        assert!(
+            matches!(outcome, IngestOutcome::Skipped(SkipReason::NoText)),
+            "{:?}",
+            outcome
+        );
+        assert!(ai.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn dry_run_schedules_no_reminders() {
+        let path = temp_path("reminders.json");
+        let reminders = Arc::new(ReminderStore::load(&path).await.unwrap());
+        let call = "<｜tool▁calls▁begin｜><｜tool▁call▁begin｜>function<｜tool▁sep｜>reminder\n```json\n{\"message\": \"stretch\", \"in\": \"1h\"}\n```<｜tool▁call▁end｜><｜tool▁calls▁end｜>";
+        let ai = Arc::new(MockAiService::with_responses([call, "will do"]));
+        let config = ListenerConfig {
+            dry_run: true,
+            ..Default::default()
+        };
+        let listener = listener_with_reminders(config, ai.clone(), reminders.clone()).await;
+        let root = format!("at://{}/app.bsky.feed.post/root", USER_DID);
+        let parent = format!("at://{}/app.bsky.feed.post/parent", BOT_DID);
+        listener.threads.insert(
+            &root,
+            vec![
+                post(&root, USER_DID, "remind me to stretch in an hour?"),
+                post(&parent, BOT_DID, "sure, when?"),
+            ],
+        );
+
+        let outcome = listener
+            .process(reply_event(&root, &parent, "in an hour please?"))
+            .await
+            .unwrap();
+        assert!(
+            matches!(outcome, IngestOutcome::Skipped(SkipReason::DryRun)),
+            "{:?}",
+            outcome
+        );
+        let requests = ai.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(format!("{:?}", requests[1]).contains("dry_run"));
+        assert!(reminders.take_due(i64::MAX).await.unwrap().is_empty());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn sampled_delays_stay_in_range() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(1457);
+        let delay = ReplyDelay {
+            min: Duration::from_millis(500),
+            max: Duration::from_secs(3),
+        };
+        for _ in 0..1000 {
+            let wait = delay.sample(&mut rng);
+            assert!(
+                delay.min <= wait && wait <= delay.max,
+                "{:?} out of range",
+                wait
+            );
+        }
+
+        let fixed = ReplyDelay {
+            min: Duration::from_secs(2),
+            max: Duration::from_secs(2),
+        };
+        assert_eq!(fixed.sample(&mut rng), Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn shutdown_cuts_reply_delay_short() {
+        let config = ListenerConfig {
+            reply_delay: Some(ReplyDelay {
+                min: Duration::from_secs(3600),
+                max: Duration::from_secs(3600),
+            }),
+            ..Default::default()
+        };
+        let listener = listener(config, Arc::new(MockAiService::new())).await;
+        let shutdown = listener.shutdown_signal();
+
+        let (waited, _) = tokio::join!(
+            tokio::time::timeout(Duration::from_secs(5), listener.wait_before_reply()),
+            async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                shutdown.send_replace(true);
+            },
+        );
+        assert!(waited.is_ok(), "reply delay ignored the shutdown");
+    }
+
+    #[test]
+    fn answer_starts_after_reasoning() {
+        assert_eq!(answer_start(""), None);
+        assert_eq!(answer_start("  <thi"), None);
+        assert_eq!(answer_start("<think>still thinking"), None);
+        assert_eq!(answer_start("<think>hm</think> hi"), Some(17));
+        assert_eq!(answer_start("hi there"), Some(0));
+    }
+
+    #[tokio::test]
+    async fn generation_stops_after_complete_tool_calls() {
+        let response = format!("<think>I should add</think> {} and then some", CALC_CALL);
+        let ai = Arc::new(MockAiService::with_responses([response]));
+        let listener = listener(ListenerConfig::default(), ai).await;
+
+        let content = listener
+            .generate(&[ChatMessage::user("what's 1+1?")], None, None)
+            .await
+            .unwrap();
+        assert_eq!(parse_tool_calls(&content).len(), 1);
+        assert!(!content.contains("and then some"), "{}", content);
+    }
+
+    #[tokio::test]
+    async fn tool_calls_inside_reasoning_dont_stop_generation() {
+        let response = format!("<think>maybe {} no</think> it's 2", CALC_CALL);
+        let ai = Arc::new(MockAiService::with_responses([response.clone()]));
+        let listener = listener(ListenerConfig::default(), ai).await;
+
+        let content = listener
+            .generate(&[ChatMessage::user("what's 1+1?")], None, None)
+            .await
+            .unwrap();
+        assert_eq!(content, response);
+    }
+}
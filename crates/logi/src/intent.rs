@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::prompt::load_system_prompt;
+
+/// Picks one of several named system prompts for each post from keyword rules, so e.g.
+/// questions get a prompt tuned for answering while banter keeps the default persona.
+///
+/// Configured by the JSON file at `INTENT_CONFIG`, with prompt paths relative to it:
+///
+/// ```json
+/// {
+///   "prompts": { "helpful": "prompts/helpful.txt" },
+///   "rules": [
+///     { "prompt": "helpful", "keywords": ["how do i", "explain"] },
+///     { "prompt": "helpful", "questions": true }
+///   ]
+/// }
+/// ```
+///
+/// Rules are tried in order and the first match wins. Posts matching none get the
+/// default prompt.
+#[derive(Debug, Clone, Default)]
+pub struct IntentRouter {
+    prompts: HashMap<String, String>,
+    rules: Vec<IntentRule>,
+}
+
+/// Routes posts matching it to the prompt named `prompt`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct IntentRule {
+    pub prompt: String,
+    /// Matches posts containing any of these, ignoring case.
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// Matches posts that ask a question.
+    #[serde(default)]
+    pub questions: bool,
+}
+
+impl IntentRule {
+    fn matches(&self, text: &str) -> bool {
+        (self.questions && text.contains('?'))
+            || self
+                .keywords
+                .iter()
+                .any(|keyword| text.contains(&keyword.to_lowercase()))
+    }
+}
+
+#[derive(Deserialize)]
+struct IntentFile {
+    prompts: HashMap<String, PathBuf>,
+    #[serde(default)]
+    rules: Vec<IntentRule>,
+}
+
+impl IntentRouter {
+    /// Loads the config at `INTENT_CONFIG`. Unset, or a config that fails to load,
+    /// routes every post to the default prompt.
+    pub fn from_env() -> Self {
+        let Some(path) = std::env::var("INTENT_CONFIG")
+            .ok()
+            .filter(|path| !path.trim().is_empty())
+        else {
+            return Self::default();
+        };
+        match Self::load(Path::new(&path)) {
+            Ok(router) => {
+                info!(
+                    "Loaded {} intent prompts and {} rules from {}",
+                    router.prompts.len(),
+                    router.rules.len(),
+                    path
+                );
+                router
+            }
+            Err(e) => {
+                warn!(
+                    "Could not load {}, using the default prompt only: {}",
+                    path, e
+                );
+                Self::default()
+            }
+        }
+    }
+
+    /// Reads the config at `path` and the prompts it names. Rules naming a prompt that
+    /// isn't defined are dropped.
+    pub fn load(path: &Path) -> Result<Self> {
+        let file: IntentFile = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+
+        let mut prompts = HashMap::new();
+        for (name, prompt_path) in file.prompts {
+            let prompt = load_system_prompt(&dir.join(&prompt_path))
+                .map_err(|e| anyhow!("prompt '{}': {}", name, e))?;
+            prompts.insert(name, prompt);
+        }
+        let rules = file
+            .rules
+            .into_iter()
+            .filter(|rule| {
+                let known = prompts.contains_key(&rule.prompt);
+                if !known {
+                    warn!("Ignoring intent rule for unknown prompt '{}'", rule.prompt);
+                }
+                known
+            })
+            .collect();
+
+        Ok(Self { prompts, rules })
+    }
+
+    /// The named prompts, to register with the LLM service.
+    pub fn prompts(&self) -> &HashMap<String, String> {
+        &self.prompts
+    }
+
+    /// The name of the prompt for a post with `text`, or `None` for the default one.
+    pub fn route(&self, text: &str) -> Option<&str> {
+        let text = text.to_lowercase();
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(&text))
+            .map(|rule| rule.prompt.as_str())
+    }
+}
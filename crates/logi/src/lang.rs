@@ -0,0 +1,151 @@
+use atrium_api::types::string::Language;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use whatlang::Lang;
+
+const FALLBACK_LANG: &str = "en"; // Used when neither detection nor the post says otherwise
+
+/// Mentions, links and hashtags, which say nothing about the language a post is in.
+static NOISE_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(@[\w.:-]+|https?://\S+|#\w+)").unwrap());
+
+/// Detects the language of `text`, or `None` if it's too short or mixed to tell
+/// reliably. Mentions, links and hashtags are ignored.
+pub fn detect_language(text: &str) -> Option<Language> {
+    let text = NOISE_RE.replace_all(text, " ");
+    let info = whatlang::detect(&text)?;
+    if !info.is_reliable() {
+        return None;
+    }
+    Language::new(language_tag(info.lang()).to_string()).ok()
+}
+
+/// Picks the `langs` for a reply to a post: the language detected from `post_text` if
+/// detection is reliable, otherwise the languages the post declared, otherwise English.
+pub fn resolve_reply_langs(post_text: &str, declared: Option<Vec<Language>>) -> Vec<Language> {
+    if let Some(detected) = detect_language(post_text) {
+        return vec![detected];
+    }
+    match declared {
+        Some(declared) if !declared.is_empty() => declared,
+        _ => vec![Language::new(FALLBACK_LANG.to_string()).unwrap()],
+    }
+}
+
+/// The BCP-47 tag for `lang`: its two-letter ISO 639-1 code where it has one, otherwise
+/// the three-letter code whatlang uses.
+fn language_tag(lang: Lang) -> &'static str {
+    match lang {
+        Lang::Afr => "af",
+        Lang::Aka => "ak",
+        Lang::Amh => "am",
+        Lang::Ara => "ar",
+        Lang::Aze => "az",
+        Lang::Bel => "be",
+        Lang::Ben => "bn",
+        Lang::Bul => "bg",
+        Lang::Cat => "ca",
+        Lang::Ces => "cs",
+        Lang::Cmn => "zh",
+        Lang::Cym => "cy",
+        Lang::Dan => "da",
+        Lang::Deu => "de",
+        Lang::Ell => "el",
+        Lang::Eng => "en",
+        Lang::Epo => "eo",
+        Lang::Est => "et",
+        Lang::Fin => "fi",
+        Lang::Fra => "fr",
+        Lang::Guj => "gu",
+        Lang::Heb => "he",
+        Lang::Hin => "hi",
+        Lang::Hrv => "hr",
+        Lang::Hun => "hu",
+        Lang::Hye => "hy",
+        Lang::Ind => "id",
+        Lang::Ita => "it",
+        Lang::Jav => "jv",
+        Lang::Jpn => "ja",
+        Lang::Kan => "kn",
+        Lang::Kat => "ka",
+        Lang::Khm => "km",
+        Lang::Kor => "ko",
+        Lang::Lat => "la",
+        Lang::Lav => "lv",
+        Lang::Lit => "lt",
+        Lang::Mal => "ml",
+        Lang::Mar => "mr",
+        Lang::Mkd => "mk",
+        Lang::Mya => "my",
+        Lang::Nep => "ne",
+        Lang::Nld => "nl",
+        Lang::Nob => "nb",
+        Lang::Ori => "or",
+        Lang::Pan => "pa",
+        Lang::Pes => "fa",
+        Lang::Pol => "pl",
+        Lang::Por => "pt",
+        Lang::Ron => "ro",
+        Lang::Rus => "ru",
+        Lang::Sin => "si",
+        Lang::Slk => "sk",
+        Lang::Slv => "sl",
+        Lang::Sna => "sn",
+        Lang::Spa => "es",
+        Lang::Srp => "sr",
+        Lang::Swe => "sv",
+        Lang::Tam => "ta",
+        Lang::Tel => "te",
+        Lang::Tgl => "tl",
+        Lang::Tha => "th",
+        Lang::Tuk => "tk",
+        Lang::Tur => "tr",
+        Lang::Ukr => "uk",
+        Lang::Urd => "ur",
+        Lang::Uzb => "uz",
+        Lang::Vie => "vi",
+        Lang::Yid => "yi",
+        Lang::Zul => "zu",
+        other => other.code(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn langs(tags: &[&str]) -> Vec<Language> {
+        tags.iter()
+            .map(|tag| Language::new(tag.to_string()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn detected_language_wins_over_declared() {
+        let text =
+            "Bonjour à tous, je voudrais savoir comment fonctionne cette application exactement.";
+        assert_eq!(
+            resolve_reply_langs(text, Some(langs(&["en"]))),
+            langs(&["fr"])
+        );
+    }
+
+    #[test]
+    fn mentions_and_links_are_ignored() {
+        let text = "@aigis.bsky.social https://example.com/some/long/path ¿Puedes explicarme qué significa esta palabra en español?";
+        assert_eq!(resolve_reply_langs(text, None), langs(&["es"]));
+    }
+
+    #[test]
+    fn undetectable_text_uses_declared_langs() {
+        assert_eq!(
+            resolve_reply_langs("ok 👍", Some(langs(&["de", "en"]))),
+            langs(&["de", "en"])
+        );
+    }
+
+    #[test]
+    fn falls_back_to_english() {
+        assert_eq!(resolve_reply_langs("ok 👍", None), langs(&["en"]));
+        assert_eq!(resolve_reply_langs("ok 👍", Some(vec![])), langs(&["en"]));
+    }
+}
@@ -1,32 +1,83 @@
 #![allow(unused)]
 
+pub mod allowlist;
+pub mod autopost;
 pub mod cursor;
 pub mod embed;
 pub mod ingestors;
+pub mod intent;
 pub mod kv;
+pub mod lang;
 pub mod llm;
+#[cfg(any(test, feature = "test-util"))]
+pub mod mock;
+pub mod post;
+pub mod prompt;
+pub mod reminders;
+pub mod sanitize;
+pub mod thread_cache;
 pub mod tools;
 pub mod vdb;
 
+use std::time::Duration;
+
 use anyhow::Result;
 use atrium_api::types::string::Did;
 use bsky_sdk::BskyAgent;
-use metrics;
 use metrics_exporter_prometheus::PrometheusBuilder;
 use once_cell::sync::Lazy;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+
+/// Reads a boolean environment variable: `1`/`true`/`yes`/`on` or `0`/`false`/`no`/`off`,
+/// case-insensitively. Returns `None` when unset or unrecognised.
+pub fn env_flag(var: &str) -> Option<bool> {
+    let value = std::env::var(var).ok()?;
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => {
+            warn!(
+                "Ignoring invalid {} '{}', expected true or false",
+                var, value
+            );
+            None
+        }
+    }
+}
 
 /// Initialize tracing subscriber for logging.
 pub fn setup_tracing() {
     tracing_subscriber::fmt::init();
 }
 
-static POSTS_INGESTED: Lazy<metrics::Counter> =
+pub(crate) static POSTS_INGESTED: Lazy<metrics::Counter> =
     Lazy::new(|| metrics::counter!("posts_ingested_total"));
-static INGEST_ERRORS: Lazy<metrics::Counter> =
+pub(crate) static REPLIES_POSTED: Lazy<metrics::Counter> =
+    Lazy::new(|| metrics::counter!("replies_posted_total"));
+pub(crate) static MEMORY_WRITES_SKIPPED: Lazy<metrics::Counter> =
+    Lazy::new(|| metrics::counter!("memory_writes_skipped_total"));
+pub(crate) static EMBEDDER_DEGRADED: Lazy<metrics::Gauge> =
+    Lazy::new(|| metrics::gauge!("embedder_degraded"));
+pub(crate) static INGEST_ERRORS: Lazy<metrics::Counter> =
     Lazy::new(|| metrics::counter!("ingest_errors_total"));
-static INGEST_LATENCY: Lazy<metrics::Histogram> =
+pub(crate) static INGEST_LATENCY: Lazy<metrics::Histogram> =
     Lazy::new(|| metrics::histogram!("ingest_latency_seconds"));
+pub(crate) static THREAD_FETCH_LATENCY: Lazy<metrics::Histogram> =
+    Lazy::new(|| metrics::histogram!("thread_fetch_seconds"));
+pub(crate) static EMBED_LATENCY: Lazy<metrics::Histogram> =
+    Lazy::new(|| metrics::histogram!("embed_seconds"));
+pub(crate) static VDB_SEARCH_LATENCY: Lazy<metrics::Histogram> =
+    Lazy::new(|| metrics::histogram!("vdb_search_seconds"));
+pub(crate) static TOOL_LOOP_ITERATIONS: Lazy<metrics::Histogram> =
+    Lazy::new(|| metrics::histogram!("tool_loop_iterations"));
+pub(crate) static LLM_FIRST_TOKEN_LATENCY: Lazy<metrics::Histogram> =
+    Lazy::new(|| metrics::histogram!("llm_first_token_seconds"));
+pub(crate) static LLM_LATENCY: Lazy<metrics::Histogram> =
+    Lazy::new(|| metrics::histogram!("llm_seconds"));
+
+pub const LOGIN_ATTEMPTS: u32 = 5; // Bluesky login attempts before giving up
+const LOGIN_RETRY_BASE_DELAY: Duration = Duration::from_secs(2); // Doubled after each failed attempt
+const SESSION_REFRESH_INTERVAL: Duration = Duration::from_secs(30 * 60); // Access tokens last ~2 hours
 
 /// Initialize Prometheus metrics exporter.
 pub fn setup_metrics() {
@@ -44,11 +95,93 @@ pub async fn setup_bsky_sess() -> Result<(BskyAgent, Did)> {
     let _enter = span.enter();
 
     let agent = BskyAgent::builder().build().await?;
+    let did = login(&agent).await?;
+
+    Ok((agent, did))
+}
+
+/// Starts a fresh session on an existing agent using the configured credentials.
+pub async fn login(agent: &BskyAgent) -> Result<Did> {
     let res = agent
         .login(std::env::var("ATP_USER")?, std::env::var("ATP_PASSWORD")?)
         .await?;
 
     info!("logged in as {}", res.handle.to_string());
 
-    Ok((agent, res.did.to_owned()))
+    Ok(res.did.to_owned())
+}
+
+/// Whether an XRPC error means the session is no longer valid.
+pub fn is_auth_error(e: &impl std::fmt::Display) -> bool {
+    let msg = e.to_string();
+    [
+        "ExpiredToken",
+        "InvalidToken",
+        "AuthenticationRequired",
+        "AuthMissing",
+    ]
+    .iter()
+    .any(|code| msg.contains(code))
+}
+
+/// Periodically checks the session so the access token is refreshed before it lapses.
+///
+/// The agent refreshes an expired access token by itself on the next request; this
+/// keeps that happening while the bot is idle, and logs in again from scratch if the
+/// refresh token has expired too.
+pub fn spawn_session_refresh(agent: BskyAgent) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(SESSION_REFRESH_INTERVAL).await;
+            if let Err(e) = agent.api.com.atproto.server.get_session().await {
+                warn!("Bluesky session check failed, logging in again: {}", e);
+                if let Err(e) = login(&agent).await {
+                    error!("Failed to refresh Bluesky session: {}", e);
+                }
+            }
+        }
+    });
+}
+
+/// Logs in to Bluesky, retrying with exponential backoff before giving up.
+pub async fn setup_bsky_sess_with_retry() -> Result<(BskyAgent, Did)> {
+    let mut delay = LOGIN_RETRY_BASE_DELAY;
+    let mut attempt = 1;
+    loop {
+        match setup_bsky_sess().await {
+            Ok(r) => return Ok(r),
+            Err(e) if attempt < LOGIN_ATTEMPTS => {
+                warn!(
+                    "Bluesky login attempt {}/{} failed: {}, retrying in {:?}",
+                    attempt, LOGIN_ATTEMPTS, e, delay
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Creates a post, logging in again and retrying once if the session has expired.
+/// Text over Bluesky's length limits is refused here rather than by the API.
+pub async fn create_post(
+    agent: &BskyAgent,
+    record: atrium_api::app::bsky::feed::post::RecordData,
+) -> Result<String> {
+    anyhow::ensure!(
+        post::fits_bluesky_limit(&record.text),
+        "Post text is over Bluesky's length limit ({} bytes)",
+        record.text.len()
+    );
+    match agent.create_record(record.clone()).await {
+        Ok(output) => Ok(output.data.uri),
+        Err(e) if is_auth_error(&e) => {
+            warn!("Session expired while posting, logging in again: {}", e);
+            login(agent).await?;
+            Ok(agent.create_record(record).await?.data.uri)
+        }
+        Err(e) => Err(e.into()),
+    }
 }
@@ -1,24 +1,28 @@
+use std::collections::HashMap;
 use std::pin::Pin;
 
 use crate::tools::AiTool;
 use anyhow::{anyhow, Error};
 use async_trait::async_trait;
 use futures_util::StreamExt;
-use genai::chat::{ChatMessage, ChatRequest};
+use genai::chat::{ChatMessage, ChatOptions, ChatRequest};
 use genai::resolver::{AuthData, Endpoint, ServiceTargetResolver};
 use genai::{adapter::AdapterKind, Client, ModelIden, ServiceTarget};
+use tracing::{trace, warn};
+
+pub use genai::chat::ReasoningEffort;
 
 #[async_trait]
 pub trait AiService {
     async fn generate_response(
         &self,
-        messages: &Vec<ChatMessage>,
-        searched_messages: Option<&Vec<ChatMessage>>,
+        messages: &[ChatMessage],
+        searched_messages: Option<&[ChatMessage]>,
     ) -> Result<String, Error>;
     async fn generate_response_stream<'a>(
         &'a self,
-        messages: &'a Vec<ChatMessage>,
-        searched_messages: Option<&'a Vec<ChatMessage>>,
+        messages: &'a [ChatMessage],
+        searched_messages: Option<&'a [ChatMessage]>,
     ) -> Result<
         Pin<
             Box<
@@ -33,11 +37,84 @@ pub trait AiService {
 
 pub struct LLMService {
     client: Client,
-    system_prompt: Option<String>,
-    pub tools: Vec<Box<dyn AiTool>>,
+    /// The prompt as given by the caller, before tool context is merged in.
+    user_prompt: Option<String>,
+    /// Alternatives to `user_prompt` that single requests can ask for by name.
+    named_prompts: HashMap<String, String>,
+    /// Tool-calling instructions for `tools`, regenerated whenever the tool set changes.
+    tool_context: String,
+    tools: Vec<Box<dyn AiTool>>,
     provider: String,
+    /// Requested reasoning effort; providers that don't support it ignore it.
+    reasoning_effort: Option<ReasoningEffort>,
+    tool_prompt_style: ToolPromptStyle,
+    prompt_layout: PromptLayout,
+}
+
+const SEARCH_RESULTS_START: &str =
+    "The following messages may help you when responding to the user. You can use them, or not.";
+const SEARCH_RESULTS_END: &str =
+    "End of search results. Following are messages from the conversation thread history.";
+
+/// The order the system prompt, retrieved context and conversation are sent in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PromptLayout {
+    /// System prompt, then the retrieved context between two system notes, then the
+    /// conversation.
+    #[default]
+    PersonaFirst,
+    /// Retrieved context and conversation first, with the system prompt last so it's
+    /// the freshest instruction.
+    PersonaLast,
+    /// System prompt first, then the retrieved context folded into one user message,
+    /// for models that weigh system messages oddly.
+    ContextAsUser,
 }
 
+impl PromptLayout {
+    /// Reads `PROMPT_LAYOUT` (`persona-first`, `persona-last` or `context-as-user`).
+    pub fn from_env() -> Self {
+        match std::env::var("PROMPT_LAYOUT") {
+            Ok(v) if v.eq_ignore_ascii_case("persona-last") => Self::PersonaLast,
+            Ok(v) if v.eq_ignore_ascii_case("context-as-user") => Self::ContextAsUser,
+            Ok(v) if !v.eq_ignore_ascii_case("persona-first") && !v.is_empty() => {
+                warn!("Unknown PROMPT_LAYOUT '{}', using persona-first", v);
+                Self::PersonaFirst
+            }
+            _ => Self::PersonaFirst,
+        }
+    }
+}
+
+/// How the tool list is rendered into the system prompt. Tool calls use the same
+/// format either way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ToolPromptStyle {
+    /// A bulleted list of names and free-text descriptions, as DeepSeek models expect.
+    #[default]
+    Text,
+    /// A JSON array of `{name, description, parameters}`, with each tool's argument
+    /// schema. Tends to work better for models other than DeepSeek.
+    Json,
+}
+
+impl ToolPromptStyle {
+    /// Reads `TOOL_PROMPT_STYLE` (`text` or `json`).
+    pub fn from_env() -> Self {
+        match std::env::var("TOOL_PROMPT_STYLE") {
+            Ok(v) if v.eq_ignore_ascii_case("json") => Self::Json,
+            Ok(v) if !v.eq_ignore_ascii_case("text") && !v.is_empty() => {
+                warn!("Unknown TOOL_PROMPT_STYLE '{}', using text", v);
+                Self::Text
+            }
+            _ => Self::Text,
+        }
+    }
+}
+
+/// Model used when none is configured.
+pub const DEFAULT_MODEL: &str = "DeepSeek-R1-0528";
+
 const AKASH_MODELS: [&str; 2] = ["Qwen3-235B-A22B-FP8", "DeepSeek-R1-0528"];
 
 impl LLMService {
@@ -65,83 +142,241 @@ impl LLMService {
             },
         );
 
-        // Compose tool context for the system prompt
-        let mut tool_context = String::new();
-        if !tools.is_empty() {
-            let tool_list: String = tools
-                .iter()
-                .map(|tool| format!("* `{}`: {}", tool.name(), tool.description()))
-                .collect::<Vec<String>>()
-                .join("\n");
-            tool_context = format!("\
-            **About tool calling**
-            You have access to the following tools:
-            {tool_list}
-
-            When calling tools, you MUST output the function call(s) in the following format, with NO deviations, substitutions, or omissions. The format must be followed EXACTLY, including all special tokens, triple backticks, and the json language identifier.
-            For a single function call, output:
-            <｜tool▁calls▁begin｜><｜tool▁call▁begin｜>function<｜tool▁sep｜>function_name
-            ```json
-            {{\"param1\": \"value1\", \"param2\": \"value2\"}}
-            ```
-            <｜tool▁call▁end｜><｜tool▁calls▁end｜>
-
-            For multiple function calls, do:
-            <｜tool▁calls▁begin｜><｜tool▁call▁begin｜>function<｜tool▁sep｜>function_name
-            ```json
-            {{\"param1\": \"value1\", \"param2\": \"value2\"}}
-            ```
-            <｜tool▁call▁end｜><｜tool▁call▁begin｜>function<｜tool▁sep｜>function_name
-            ```json
-            {{\"param1\": \"value1\", \"param2\": \"value2\"}}
-            ```
-            <｜tool▁call▁end｜><｜tool▁calls▁end｜>
-
-            Do not add, remove, or change any part of this format. Do not simulate tool calls in any other way. Only use this format for tool calls.");
-        }
-
-        let merged_prompt = match system_prompt {
-            Some(user_prompt) if !user_prompt.trim().is_empty() => {
-                format!("{}\n\n{}", tool_context, user_prompt)
-            }
-            _ => tool_context,
-        };
-
         let client = Client::builder()
             .with_service_target_resolver(akash_resolver)
             .build();
 
+        let tool_prompt_style = ToolPromptStyle::from_env();
         Ok(LLMService {
             provider: provider.to_string(),
             client,
-            system_prompt: Some(merged_prompt),
+            user_prompt: system_prompt.map(str::to_string),
+            named_prompts: HashMap::new(),
+            tool_context: tool_context(&tools, tool_prompt_style),
             tools,
+            reasoning_effort: reasoning_effort_from_env(),
+            tool_prompt_style,
+            prompt_layout: PromptLayout::from_env(),
         })
     }
 
     pub fn add_tool(&mut self, tool: Box<dyn AiTool>) {
         self.tools.push(tool);
+        self.refresh_tool_context();
     }
 
     pub fn remove_tool(&mut self, tool_name: &str) -> Result<(), Error> {
         if let Some(pos) = self.tools.iter().position(|t| t.name() == tool_name) {
             self.tools.remove(pos);
+            self.refresh_tool_context();
             Ok(())
         } else {
             Err(anyhow!("Tool not found: {}", tool_name))
         }
     }
 
+    /// Removes the tool named `tool_name` and hands it back, so it can be added again
+    /// later with [`LLMService::add_tool`].
+    pub fn take_tool(&mut self, tool_name: &str) -> Option<Box<dyn AiTool>> {
+        let pos = self.tools.iter().position(|t| t.name() == tool_name)?;
+        let tool = self.tools.remove(pos);
+        self.refresh_tool_context();
+        Some(tool)
+    }
+
+    /// Removes every tool, dropping the tool-calling instructions from the system prompt.
+    pub fn clear_tools(&mut self) {
+        self.tools.clear();
+        self.refresh_tool_context();
+    }
+
+    /// Replaces the whole tool set and rewrites the system prompt to describe it.
+    pub fn set_tools(&mut self, tools: Vec<Box<dyn AiTool>>) {
+        self.tools = tools;
+        self.refresh_tool_context();
+    }
+
+    /// Rebuilds the tool context so it matches `self.tools`.
+    fn refresh_tool_context(&mut self) {
+        self.tool_context = tool_context(&self.tools, self.tool_prompt_style);
+    }
+
+    /// Switches how tools are listed in the system prompt.
+    pub fn set_tool_prompt_style(&mut self, style: ToolPromptStyle) {
+        self.tool_prompt_style = style;
+        self.refresh_tool_context();
+    }
+
+    pub fn tool_prompt_style(&self) -> ToolPromptStyle {
+        self.tool_prompt_style
+    }
+
+    /// Switches the order the prompt, retrieved context and conversation are sent in.
+    pub fn set_prompt_layout(&mut self, layout: PromptLayout) {
+        self.prompt_layout = layout;
+    }
+
+    pub fn prompt_layout(&self) -> PromptLayout {
+        self.prompt_layout
+    }
+
+    /// Puts the system prompt, `searched_messages` and `messages` together in the
+    /// order of the configured [`PromptLayout`]. The system prompt is the one named
+    /// `prompt` if given and registered, otherwise the usual one.
+    fn assemble_messages(
+        &self,
+        messages: &[ChatMessage],
+        searched_messages: Option<&[ChatMessage]>,
+        prompt: Option<&str>,
+    ) -> Vec<ChatMessage> {
+        let user_prompt = prompt
+            .and_then(|name| self.named_prompts.get(name))
+            .map(String::as_str)
+            .or(self.user_prompt.as_deref());
+        let system = ChatMessage::system(merge_prompt(user_prompt, &self.tool_context));
+        let mut context = Vec::new();
+        if let Some(searched) = searched_messages {
+            if self.prompt_layout == PromptLayout::ContextAsUser {
+                let texts: Vec<&str> = searched
+                    .iter()
+                    .filter_map(|m| m.content.text_as_str())
+                    .collect();
+                context.push(ChatMessage::user(format!(
+                    "{}\n\n{}\n\n{}",
+                    SEARCH_RESULTS_START,
+                    texts.join("\n"),
+                    SEARCH_RESULTS_END
+                )));
+            } else {
+                context.push(ChatMessage::system(SEARCH_RESULTS_START));
+                context.extend(searched.iter().cloned());
+                context.push(ChatMessage::system(SEARCH_RESULTS_END));
+            }
+        }
+
+        let mut all_msgs = Vec::with_capacity(messages.len() + context.len() + 1);
+        if self.prompt_layout != PromptLayout::PersonaLast {
+            all_msgs.push(system.clone());
+        }
+        all_msgs.extend(context);
+        all_msgs.extend(messages.iter().cloned());
+        if self.prompt_layout == PromptLayout::PersonaLast {
+            all_msgs.push(system);
+        }
+
+        // the exact prompt the model sees; only rendered when someone's looking
+        if tracing::enabled!(tracing::Level::TRACE) {
+            trace!(
+                model = %self.provider,
+                messages = all_msgs.len(),
+                "assembled prompt:\n{}",
+                render_messages(&all_msgs)
+            );
+        }
+        all_msgs
+    }
+
+    /// Returns the registered tools.
+    pub fn tools(&self) -> &[Box<dyn AiTool>] {
+        &self.tools
+    }
+
+    /// Returns the model currently targeted by this service.
+    pub fn model(&self) -> &str {
+        &self.provider
+    }
+
+    /// Switches the target model, keeping the system prompt and tools intact.
+    pub fn set_model(&mut self, model: &str) {
+        self.provider = model.to_string();
+    }
+
+    /// Asks the model to spend less (or more) on hidden reasoning, which for the bot
+    /// means faster, cheaper replies. Sent as `reasoning_effort` to OpenAI-compatible
+    /// providers and silently ignored by the rest. `None` leaves it to the provider.
+    pub fn set_reasoning_effort(&mut self, effort: Option<ReasoningEffort>) {
+        self.reasoning_effort = effort;
+    }
+
+    pub fn reasoning_effort(&self) -> Option<&ReasoningEffort> {
+        self.reasoning_effort.as_ref()
+    }
+
+    fn chat_options(&self) -> Option<ChatOptions> {
+        self.reasoning_effort
+            .clone()
+            .map(|effort| ChatOptions::default().with_reasoning_effort(effort))
+    }
+
+    /// Replaces the user-supplied prompt; the tool context is kept separately.
     pub fn set_system_prompt(&mut self, prompt: String) {
-        self.system_prompt = Some(prompt);
+        self.user_prompt = Some(prompt);
+    }
+
+    /// Registers a prompt that requests can use instead of the usual one by passing
+    /// `name` to [`LLMService::generate_response_stream_with_prompt`].
+    pub fn set_named_prompt(&mut self, name: &str, prompt: String) {
+        self.named_prompts.insert(name.to_string(), prompt);
+    }
+
+    /// Like [`AiService::generate_response_stream`], but with the prompt registered as
+    /// `prompt` in place of the usual one. An unknown name falls back to the usual one.
+    pub async fn generate_response_stream_with_prompt<'a>(
+        &'a self,
+        prompt: Option<&str>,
+        messages: &'a [ChatMessage],
+        searched_messages: Option<&'a [ChatMessage]>,
+    ) -> Result<
+        Pin<
+            Box<
+                dyn futures_core::Stream<Item = Result<genai::chat::ChatStreamEvent, anyhow::Error>>
+                    + Send
+                    + 'a,
+            >,
+        >,
+        anyhow::Error,
+    > {
+        let chat_req =
+            ChatRequest::new(self.assemble_messages(messages, searched_messages, prompt));
+
+        let chat_stream_response = self
+            .client
+            .exec_chat_stream(&self.provider, chat_req, self.chat_options().as_ref())
+            .await?;
+
+        let mapped_stream = chat_stream_response
+            .stream
+            .map(|event_result| event_result.map_err(anyhow::Error::from));
+        Ok(Box::pin(mapped_stream))
+    }
+
+    /// Returns the effective system prompt: the tool context followed by the user prompt.
+    pub fn system_prompt(&self) -> String {
+        merge_prompt(self.user_prompt.as_deref(), &self.tool_context)
+    }
+
+    /// Returns the user-supplied prompt without any tool context.
+    pub fn user_prompt(&self) -> Option<&str> {
+        self.user_prompt.as_deref()
     }
 
     pub fn list_tools(&self) -> Vec<String> {
         self.tools.iter().map(|t| t.name().to_string()).collect()
     }
 
-    pub fn find_tool(&self, tool_name: &str) -> Option<&Box<dyn AiTool>> {
-        self.tools.iter().find(|t| t.name() == tool_name)
+    /// Returns `(name, description)` pairs for every registered tool.
+    pub fn list_tools_detailed(&self) -> Vec<(String, String)> {
+        self.tools
+            .iter()
+            .map(|t| (t.name().to_string(), t.description().to_string()))
+            .collect()
+    }
+
+    pub fn find_tool(&self, tool_name: &str) -> Option<&dyn AiTool> {
+        self.tools
+            .iter()
+            .find(|t| t.name() == tool_name)
+            .map(|t| t.as_ref())
     }
 }
 
@@ -149,29 +384,14 @@ impl LLMService {
 impl AiService for LLMService {
     async fn generate_response(
         &self,
-        messages: &Vec<ChatMessage>,
-        searched_messages: Option<&Vec<ChatMessage>>,
+        messages: &[ChatMessage],
+        searched_messages: Option<&[ChatMessage]>,
     ) -> Result<String, Error> {
-        let mut all_msgs = vec![ChatMessage::system(
-            self.system_prompt.clone().unwrap_or_default(),
-        )];
-
-        if let Some(searched_msgs) = searched_messages {
-            all_msgs.push(ChatMessage::system(
-                "The following messages may help you when responding to the user. You can use them, or not.",
-            ));
-            all_msgs.extend(searched_msgs.to_owned());
-            all_msgs.push(ChatMessage::system(
-                "End of search results. Following are messages from the conversation thread history.",
-            ));
-        }
-
-        all_msgs.extend(messages.to_owned());
-        let chat_req = ChatRequest::new(all_msgs);
+        let chat_req = ChatRequest::new(self.assemble_messages(messages, searched_messages, None));
 
         let chat_response = self
             .client
-            .exec_chat(&self.provider, chat_req, None)
+            .exec_chat(&self.provider, chat_req, self.chat_options().as_ref())
             .await?;
 
         if let Some(content) = chat_response.content {
@@ -187,8 +407,8 @@ impl AiService for LLMService {
 
     async fn generate_response_stream<'a>(
         &'a self,
-        messages: &'a Vec<ChatMessage>,
-        searched_messages: Option<&'a Vec<ChatMessage>>,
+        messages: &'a [ChatMessage],
+        searched_messages: Option<&'a [ChatMessage]>,
     ) -> Result<
         Pin<
             Box<
@@ -199,31 +419,102 @@ impl AiService for LLMService {
         >,
         anyhow::Error,
     > {
-        let mut all_msgs = vec![ChatMessage::system(
-            self.system_prompt.clone().unwrap_or_default(),
-        )];
-
-        if let Some(searched_msgs) = searched_messages {
-            all_msgs.push(ChatMessage::system(
-                "The following messages may help you when responding to the user. You can use them, or not.",
-            ));
-            all_msgs.extend(searched_msgs.to_owned());
-            all_msgs.push(ChatMessage::system(
-                "End of search results. Following are messages from the conversation thread history.",
-            ));
+        self.generate_response_stream_with_prompt(None, messages, searched_messages)
+            .await
+    }
+}
+
+/// Renders `messages` one per block as `[role] text`, for logging.
+fn render_messages(messages: &[ChatMessage]) -> String {
+    messages
+        .iter()
+        .map(|message| {
+            let text = message
+                .content
+                .text_as_str()
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("{:?}", message.content));
+            format!("[{:?}] {}", message.role, text)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Reads `REASONING_EFFORT` (`low`, `medium` or `high`).
+fn reasoning_effort_from_env() -> Option<ReasoningEffort> {
+    let value = std::env::var("REASONING_EFFORT").ok()?;
+    let effort = ReasoningEffort::from_lower_str(&value.trim().to_ascii_lowercase());
+    if effort.is_none() {
+        warn!(
+            "Ignoring invalid REASONING_EFFORT '{}', expected low, medium or high",
+            value
+        );
+    }
+    effort
+}
+
+/// Builds the tool-calling instructions listing `tools`, or an empty string if there are none.
+fn tool_context(tools: &[Box<dyn AiTool>], style: ToolPromptStyle) -> String {
+    if tools.is_empty() {
+        return String::new();
+    }
+    let tool_list: String = match style {
+        ToolPromptStyle::Text => tools
+            .iter()
+            .map(|tool| format!("* `{}`: {}", tool.name(), tool.description()))
+            .collect::<Vec<String>>()
+            .join("\n"),
+        ToolPromptStyle::Json => {
+            let manifest: Vec<serde_json::Value> = tools
+                .iter()
+                .map(|tool| {
+                    serde_json::json!({
+                        "name": tool.name(),
+                        "description": tool.description().trim(),
+                        "parameters": tool.parameters(),
+                    })
+                })
+                .collect();
+            format!(
+                "```json\n{}\n```",
+                serde_json::to_string_pretty(&manifest).unwrap_or_default()
+            )
         }
+    };
+    format!("\
+    **About tool calling**
+    You have access to the following tools:
+    {tool_list}
 
-        all_msgs.extend(messages.to_owned());
-        let chat_req = ChatRequest::new(all_msgs);
+    When calling tools, you MUST output the function call(s) in the following format, with NO deviations, substitutions, or omissions. The format must be followed EXACTLY, including all special tokens, triple backticks, and the json language identifier.
+    For a single function call, output:
+    <｜tool▁calls▁begin｜><｜tool▁call▁begin｜>function<｜tool▁sep｜>function_name
+    ```json
+    {{\"param1\": \"value1\", \"param2\": \"value2\"}}
+    ```
+    <｜tool▁call▁end｜><｜tool▁calls▁end｜>
 
-        let chat_stream_response = self
-            .client
-            .exec_chat_stream(&self.provider, chat_req, None)
-            .await?;
+    For multiple function calls, do:
+    <｜tool▁calls▁begin｜><｜tool▁call▁begin｜>function<｜tool▁sep｜>function_name
+    ```json
+    {{\"param1\": \"value1\", \"param2\": \"value2\"}}
+    ```
+    <｜tool▁call▁end｜><｜tool▁call▁begin｜>function<｜tool▁sep｜>function_name
+    ```json
+    {{\"param1\": \"value1\", \"param2\": \"value2\"}}
+    ```
+    <｜tool▁call▁end｜><｜tool▁calls▁end｜>
 
-        let mapped_stream = chat_stream_response
-            .stream
-            .map(|event_result| event_result.map_err(anyhow::Error::from));
-        Ok(Box::pin(mapped_stream))
+    Do not add, remove, or change any part of this format. Do not simulate tool calls in any other way. Only use this format for tool calls.
+    Tool calls must come at the very end of your response. A call followed by more text is treated as a quoted example and is not run.")
+}
+
+/// Puts the tool context in front of the user prompt, skipping an empty user prompt.
+fn merge_prompt(user_prompt: Option<&str>, tool_context: &str) -> String {
+    match user_prompt {
+        Some(user_prompt) if !user_prompt.trim().is_empty() => {
+            format!("{}\n\n{}", tool_context, user_prompt)
+        }
+        _ => tool_context.to_string(),
     }
 }
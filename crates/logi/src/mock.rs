@@ -0,0 +1,108 @@
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::Mutex;
+
+use anyhow::Error;
+use async_trait::async_trait;
+use genai::chat::{ChatMessage, ChatStreamEvent, StreamChunk, StreamEnd};
+
+use crate::llm::AiService;
+
+/// An [`AiService`] that answers without calling a model, for tests.
+///
+/// Queued responses are returned in order; once they run out, the service echoes the
+/// text of the last message it was sent. Every request is recorded so tests can check
+/// what would have been sent to the model.
+#[derive(Default)]
+pub struct MockAiService {
+    responses: Mutex<VecDeque<String>>,
+    requests: Mutex<Vec<Vec<ChatMessage>>>,
+}
+
+impl MockAiService {
+    /// Creates a mock that only echoes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a mock that returns `responses` in order before falling back to echoing.
+    pub fn with_responses<I, S>(responses: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            responses: Mutex::new(responses.into_iter().map(Into::into).collect()),
+            requests: Mutex::default(),
+        }
+    }
+
+    /// Queues another canned response.
+    pub fn push_response(&self, response: impl Into<String>) {
+        self.responses.lock().unwrap().push_back(response.into());
+    }
+
+    /// Returns every request received so far, searched messages first as the real service sends them.
+    pub fn requests(&self) -> Vec<Vec<ChatMessage>> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn respond(
+        &self,
+        messages: &[ChatMessage],
+        searched_messages: Option<&[ChatMessage]>,
+    ) -> String {
+        let request: Vec<ChatMessage> = searched_messages
+            .unwrap_or_default()
+            .iter()
+            .chain(messages)
+            .cloned()
+            .collect();
+        self.requests.lock().unwrap().push(request);
+
+        self.responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| {
+                messages
+                    .last()
+                    .and_then(|m| m.content.text_as_str())
+                    .unwrap_or_default()
+                    .to_string()
+            })
+    }
+}
+
+#[async_trait]
+impl AiService for MockAiService {
+    async fn generate_response(
+        &self,
+        messages: &[ChatMessage],
+        searched_messages: Option<&[ChatMessage]>,
+    ) -> Result<String, Error> {
+        Ok(self.respond(messages, searched_messages))
+    }
+
+    async fn generate_response_stream<'a>(
+        &'a self,
+        messages: &'a [ChatMessage],
+        searched_messages: Option<&'a [ChatMessage]>,
+    ) -> Result<
+        Pin<Box<dyn futures_core::Stream<Item = Result<ChatStreamEvent, Error>> + Send + 'a>>,
+        Error,
+    > {
+        let response = self.respond(messages, searched_messages);
+        // Stream word by word so consumers see more than one chunk
+        let events = std::iter::once(ChatStreamEvent::Start)
+            .chain(response.split_inclusive(' ').map(|piece| {
+                ChatStreamEvent::Chunk(StreamChunk {
+                    content: piece.to_string(),
+                })
+            }))
+            .chain(std::iter::once(ChatStreamEvent::End(StreamEnd::default())))
+            .map(Ok)
+            .collect::<Vec<_>>();
+        Ok(Box::pin(futures::stream::iter(events)))
+    }
+}
@@ -0,0 +1,274 @@
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::prompt::{BLUESKY_MAX_BYTES, BLUESKY_MAX_GRAPHEMES};
+
+/// Represents basic post data for JSON serialization
+///
+/// This structure contains the essential information about a BlueSky post
+/// in a format that can be easily serialized to JSON. It excludes complex
+/// nested structures found in the original ATP/BlueSky data model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostData {
+    /// Author name and handle (formatted as "Name (handle)")
+    pub author: String,
+    /// Content of the post (the actual text)
+    pub text: String,
+    /// Unique identifier for the post (AT URI)
+    pub uri: String,
+    /// Author's DID (Decentralized Identifier)
+    pub author_did: String,
+    /// Post timestamp as an ISO-8601 string
+    pub indexed_at: Option<String>,
+
+    pub embed: Option<PostEmbed>,
+}
+
+impl PostData {
+    /// The text a post is both embedded as and stored as in memory.
+    ///
+    /// This is `author: text`, followed by whichever of the embed's `fields` the post
+    /// has. Memories store exactly this string rather than the full post JSON, so the
+    /// vector and the retrieved content always describe the same thing.
+    pub fn embedding_text(&self, fields: &EmbeddingFields) -> String {
+        let mut text = format!("{}: {}", self.author, self.text);
+        let mut push = |enabled: bool, extra: &Option<String>| {
+            if !enabled {
+                return;
+            }
+            if let Some(extra) = extra.as_deref().map(str::trim).filter(|e| !e.is_empty()) {
+                text.push('\n');
+                text.push_str(extra);
+            }
+        };
+
+        match &self.embed {
+            Some(PostEmbed::External(external)) => {
+                push(fields.link_title, &external.title);
+                push(fields.link_description, &external.description);
+            }
+            Some(PostEmbed::Images(images)) => {
+                for image in &images.images {
+                    push(fields.image_alt, &image.alt);
+                }
+            }
+            Some(PostEmbed::Record(record)) => {
+                push(fields.quote_text, &record.text);
+            }
+            Some(PostEmbed::RecordWithMedia(with_media)) => {
+                push(fields.quote_text, &with_media.record.text);
+                for media in &with_media.media {
+                    if let PostEmbedMedia::Images(images) = media {
+                        for image in &images.images {
+                            push(fields.image_alt, &image.alt);
+                        }
+                    }
+                }
+            }
+            Some(PostEmbed::Video(_)) | None => {}
+        }
+
+        text
+    }
+}
+
+/// Which parts of a post's embed are folded into its embedding text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmbeddingFields {
+    /// The title of a linked page.
+    pub link_title: bool,
+    /// The description of a linked page.
+    pub link_description: bool,
+    /// Alt text of attached images.
+    pub image_alt: bool,
+    /// Text of a quoted post, when the post view it came from included it.
+    pub quote_text: bool,
+}
+
+impl Default for EmbeddingFields {
+    fn default() -> Self {
+        Self {
+            link_title: true,
+            link_description: true,
+            image_alt: true,
+            quote_text: true,
+        }
+    }
+}
+
+impl EmbeddingFields {
+    /// Reads `EMBEDDING_FIELDS`, a comma-separated list of `link_title`,
+    /// `link_description`, `image_alt` and `quote_text`, or `none`. Defaults to all.
+    pub fn from_env() -> Self {
+        let Ok(value) = std::env::var("EMBEDDING_FIELDS") else {
+            return Self::default();
+        };
+        let mut fields = Self {
+            link_title: false,
+            link_description: false,
+            image_alt: false,
+            quote_text: false,
+        };
+        for field in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            match field.to_ascii_lowercase().as_str() {
+                "link_title" => fields.link_title = true,
+                "link_description" => fields.link_description = true,
+                "image_alt" => fields.image_alt = true,
+                "quote_text" => fields.quote_text = true,
+                "none" => {}
+                "all" => fields = Self::default(),
+                _ => warn!("Ignoring unknown EMBEDDING_FIELDS entry '{}'", field),
+            }
+        }
+        fields
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PostEmbed {
+    Images(PostEmbedImages),
+    External(PostEmbedExternal),
+    Video(PostEmbedVideo),
+    Record(PostEmbedRecord),
+    RecordWithMedia(PostEmbedRecordWithMedia),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostEmbedImages {
+    pub images: Vec<PostEmbedImage>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostEmbedImage {
+    pub image: String,       // URL to the image
+    pub alt: Option<String>, // Optional alt text for the image
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostEmbedExternal {
+    pub uri: String,                 // URL to the external content
+    pub title: Option<String>,       // Optional title for the external content
+    pub description: Option<String>, // Optional description for the external content
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostEmbedRecord {
+    pub record: String,        // AT URI to the record
+    pub title: Option<String>, // Optional title for the record
+    #[serde(default)]
+    pub text: Option<String>, // Text of the quoted post, when it came with the post view
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostEmbedRecordWithMedia {
+    pub record: PostEmbedRecord,    // AT URI to the record
+    pub media: Vec<PostEmbedMedia>, // Associated media images
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PostEmbedMedia {
+    Images(PostEmbedImages),
+    Video(PostEmbedVideo),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostEmbedVideo {
+    pub video: String,         // URL to the video
+    pub duration: Option<u64>, // Optional duration in seconds
+}
+
+/// Whether Bluesky accepts `text` as a post's text: at most [`BLUESKY_MAX_GRAPHEMES`]
+/// graphemes and [`BLUESKY_MAX_BYTES`] bytes of UTF-8.
+pub fn fits_bluesky_limit(text: &str) -> bool {
+    text.len() <= BLUESKY_MAX_BYTES && text.graphemes(true).count() <= BLUESKY_MAX_GRAPHEMES
+}
+
+/// Shortens `text` to at most `max_chars` characters and [`BLUESKY_MAX_BYTES`] bytes,
+/// cutting at the last word boundary that fits and ending with an ellipsis. Characters
+/// are never fewer than the graphemes Bluesky counts, so the result always fits a limit
+/// given in graphemes.
+pub fn truncate_reply(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars && text.len() <= BLUESKY_MAX_BYTES {
+        return text.to_string();
+    }
+    // room for the ellipsis
+    let char_budget = max_chars.saturating_sub(1);
+    let byte_budget = BLUESKY_MAX_BYTES - '…'.len_utf8();
+
+    // whole graphemes only, so an emoji sequence is never split
+    let mut end = 0;
+    let mut chars = 0;
+    for (at, grapheme) in text.grapheme_indices(true) {
+        chars += grapheme.chars().count();
+        if chars > char_budget || at + grapheme.len() > byte_budget {
+            break;
+        }
+        end = at + grapheme.len();
+    }
+    let cut = &text[..end];
+    let cut = match cut.rfind(char::is_whitespace) {
+        // don't throw away most of the reply for one long word
+        Some(space) if space > cut.len() / 2 => &cut[..space],
+        _ => cut,
+    };
+    format!("{}…", cut.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAMILY: &str = "👨\u{200d}👩\u{200d}👧\u{200d}👦"; // One grapheme, 25 bytes
+
+    #[test]
+    fn ascii_is_limited_to_300_graphemes() {
+        assert!(fits_bluesky_limit(&"a".repeat(BLUESKY_MAX_GRAPHEMES)));
+        assert!(!fits_bluesky_limit(&"a".repeat(BLUESKY_MAX_GRAPHEMES + 1)));
+    }
+
+    #[test]
+    fn cjk_counts_graphemes_not_bytes() {
+        // 900 bytes, well under the byte limit; the grapheme limit binds
+        assert!(fits_bluesky_limit(&"語".repeat(BLUESKY_MAX_GRAPHEMES)));
+        assert!(!fits_bluesky_limit(&"語".repeat(BLUESKY_MAX_GRAPHEMES + 1)));
+    }
+
+    #[test]
+    fn zwj_emoji_hit_the_byte_limit_first() {
+        let fits = BLUESKY_MAX_BYTES / FAMILY.len();
+        assert!(fits_bluesky_limit(&FAMILY.repeat(fits)));
+        // far fewer than 300 graphemes, but over 3000 bytes
+        assert!(!fits_bluesky_limit(&FAMILY.repeat(fits + 1)));
+    }
+
+    #[test]
+    fn truncated_replies_fit() {
+        let texts = [
+            "word ".repeat(200),
+            "語".repeat(1000),
+            FAMILY.repeat(200),
+            format!("{} {}", "a".repeat(150), FAMILY.repeat(200)),
+        ];
+        for text in &texts {
+            let cut = truncate_reply(text, BLUESKY_MAX_GRAPHEMES);
+            assert!(fits_bluesky_limit(&cut), "{:?} doesn't fit", cut);
+        }
+    }
+
+    #[test]
+    fn truncation_keeps_emoji_sequences_whole() {
+        // The character budget alone would allow far more than 3000 bytes here
+        let cut = truncate_reply(&FAMILY.repeat(500), 5000);
+        assert!(cut.len() <= BLUESKY_MAX_BYTES);
+        let kept = cut.strip_suffix('…').unwrap();
+        assert!(
+            kept.split(FAMILY).all(str::is_empty),
+            "a sequence was split"
+        );
+    }
+
+    #[test]
+    fn short_text_is_unchanged() {
+        assert_eq!(truncate_reply("hello 語 👋", 300), "hello 語 👋");
+    }
+}
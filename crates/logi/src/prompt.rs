@@ -0,0 +1,103 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use tokio::sync::RwLock;
+use tracing::{error, info, warn};
+
+use crate::llm::LLMService;
+
+/// Where the bot reads its persona from.
+pub const PROMPT_PATH: &str = "./prompt.txt";
+
+/// Bluesky's hard limit on post length, in graphemes.
+pub const BLUESKY_MAX_GRAPHEMES: usize = 300;
+/// Bluesky's limit on post length in UTF-8 bytes. Long emoji sequences can hit it before
+/// [`BLUESKY_MAX_GRAPHEMES`] does.
+pub const BLUESKY_MAX_BYTES: usize = 3000;
+
+/// Replaced in prompts with [`reply_max_chars`], so the limit the model is told about
+/// matches the one replies are cut to.
+pub const REPLY_MAX_CHARS_PLACEHOLDER: &str = "{reply_max_chars}";
+
+/// Reads `REPLY_MAX_CHARS`, capped at [`BLUESKY_MAX_GRAPHEMES`], which is also the default.
+pub fn reply_max_chars() -> usize {
+    std::env::var("REPLY_MAX_CHARS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&n: &usize| n > 0)
+        .map(|n| n.min(BLUESKY_MAX_GRAPHEMES))
+        .unwrap_or(BLUESKY_MAX_GRAPHEMES)
+}
+
+/// Fills in the placeholders a prompt may use.
+fn fill_placeholders(prompt: &str) -> String {
+    prompt.replace(REPLY_MAX_CHARS_PLACEHOLDER, &reply_max_chars().to_string())
+}
+
+/// The persona used when there's neither a prompt file nor `DEFAULT_PERSONA`.
+pub const DEFAULT_PERSONA: &str = "You are an unhelpful assistant, named Aigis, that acts dumb and stupid. type in lowercase, and never ever add *actions* or use emojis as they are uncool. Don't mention Persona 3 topics unless explicitly asked about it. be extremely dumb and stupid. The messages you'll see are chat logs from a platform called Bluesky, they are in the format `username (handle): message`, but you only should reply to them with the message. Keep under a strict limit of {reply_max_chars} characters.";
+
+/// Returns `DEFAULT_PERSONA` from the environment, or the built-in [`DEFAULT_PERSONA`].
+/// A prompt file, when present, takes precedence over both.
+pub fn default_persona() -> String {
+    let persona = std::env::var("DEFAULT_PERSONA")
+        .ok()
+        .filter(|persona| !persona.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_PERSONA.to_string());
+    fill_placeholders(&persona)
+}
+
+/// Reads the system prompt at `path`, fills in `{reply_max_chars}` and appends the
+/// current time.
+///
+/// A file that is empty or only whitespace is an [`std::io::ErrorKind::InvalidData`]
+/// error rather than a blank persona, so callers don't run without one by accident.
+pub fn load_system_prompt(path: &Path) -> std::io::Result<String> {
+    let content = std::fs::read_to_string(path)?;
+    if content.trim().is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{} is empty", path.display()),
+        ));
+    }
+    let current_time = time::OffsetDateTime::now_utc()
+        .format(&time::format_description::well_known::Rfc3339)
+        .unwrap_or_default();
+    Ok(fill_placeholders(&content) + &format!("\n\nCurrent time: {}", current_time))
+}
+
+/// Re-reads the prompt at `path` into `llm` whenever the process receives SIGHUP, so
+/// the persona can be tuned without restarting. A failed read keeps the old prompt.
+pub fn spawn_prompt_reload(llm: Arc<RwLock<LLMService>>, path: PathBuf) {
+    #[cfg(unix)]
+    tokio::spawn(async move {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangups = match signal(SignalKind::hangup()) {
+            Ok(hangups) => hangups,
+            Err(e) => {
+                error!("Could not listen for SIGHUP, prompt reload disabled: {}", e);
+                return;
+            }
+        };
+        while hangups.recv().await.is_some() {
+            match load_system_prompt(&path) {
+                Ok(prompt) => {
+                    llm.write().await.set_system_prompt(prompt);
+                    info!("Reloaded system prompt from {}", path.display());
+                }
+                Err(e) => warn!(
+                    "Could not reload {}, keeping the current prompt: {}",
+                    path.display(),
+                    e
+                ),
+            }
+        }
+    });
+
+    #[cfg(not(unix))]
+    warn!(
+        "Prompt reload needs SIGHUP, which isn't available here; {} is only read at startup",
+        path.display()
+    );
+}
@@ -0,0 +1,162 @@
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use atrium_api::app::bsky::feed::post::{RecordData, ReplyRef};
+use atrium_api::types::string::Datetime;
+use bsky_sdk::BskyAgent;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info};
+
+use crate::create_post;
+use crate::post::truncate_reply;
+use crate::prompt::BLUESKY_MAX_GRAPHEMES;
+use crate::sanitize::sanitize_output;
+
+const REMINDER_POLL_INTERVAL: Duration = Duration::from_secs(30); // How often due reminders are checked
+const MAX_REMINDER_ATTEMPTS: u32 = 20; // Posting attempts before a reminder is dropped, about ten minutes of polls
+
+tokio::task_local! {
+    /// The post a tool call is being made in reply to, so a reminder knows where to answer.
+    pub static REPLY_TARGET: ReplyRef;
+}
+
+/// A message to post as a reply once `due_at` has passed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reminder {
+    pub id: String,
+    /// Unix timestamp (seconds) after which the reminder is posted.
+    pub due_at: i64,
+    pub message: String,
+    pub reply: ReplyRef,
+    /// Failed posting attempts so far.
+    #[serde(default)]
+    pub attempts: u32,
+}
+
+/// Pending reminders, persisted to a JSON file so they survive restarts.
+pub struct ReminderStore {
+    path: PathBuf,
+    reminders: Mutex<Vec<Reminder>>,
+}
+
+impl ReminderStore {
+    /// Loads pending reminders from `path`, starting empty if the file doesn't exist yet.
+    pub async fn load(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let reminders: Vec<Reminder> = match tokio::fs::read_to_string(&path).await {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        info!("Loaded {} pending reminders", reminders.len());
+
+        Ok(Self {
+            path,
+            reminders: Mutex::new(reminders),
+        })
+    }
+
+    /// Loads the store from `REMINDERS_FILE` (defaults to `./reminders.json`).
+    pub async fn from_env() -> Result<Self> {
+        let path =
+            std::env::var("REMINDERS_FILE").unwrap_or_else(|_| "./reminders.json".to_string());
+        Self::load(path).await
+    }
+
+    pub async fn add(&self, reminder: Reminder) -> Result<()> {
+        self.reminders.lock().unwrap().push(reminder);
+        self.save().await
+    }
+
+    /// Removes and returns every reminder due at or before `now`.
+    pub async fn take_due(&self, now: i64) -> Result<Vec<Reminder>> {
+        let due = {
+            let mut reminders = self.reminders.lock().unwrap();
+            let (due, pending) = reminders.drain(..).partition(|r| r.due_at <= now);
+            *reminders = pending;
+            due
+        };
+        if !due.is_empty() {
+            self.save().await?;
+        }
+        Ok(due)
+    }
+
+    async fn save(&self) -> Result<()> {
+        let contents = serde_json::to_string(&*self.reminders.lock().unwrap())?;
+        tokio::fs::write(&self.path, contents).await?;
+        Ok(())
+    }
+}
+
+/// Current time as a Unix timestamp in seconds.
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+/// Periodically posts due reminders as replies to the post they were set from.
+///
+/// Reminders whose message trips the output wordlist are dropped. Ones that fail to
+/// post are put back and retried on the next poll, up to `MAX_REMINDER_ATTEMPTS`
+/// times, after which they're dropped too.
+pub fn spawn_reminder_poller(agent: BskyAgent, store: std::sync::Arc<ReminderStore>) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(REMINDER_POLL_INTERVAL).await;
+            let due = match store.take_due(unix_now()).await {
+                Ok(due) => due,
+                Err(e) => {
+                    error!("Failed to read due reminders: {}", e);
+                    continue;
+                }
+            };
+            for mut reminder in due {
+                // the message was written by the model, so it goes through the reply wordlist
+                if sanitize_output(&reminder.message).1 {
+                    info!(
+                        "Dropping reminder {}, it contains filtered words",
+                        reminder.id
+                    );
+                    continue;
+                }
+                debug!("Posting reminder {}", reminder.id);
+                let record = RecordData {
+                    created_at: Datetime::now(),
+                    embed: None,
+                    entities: None,
+                    facets: None,
+                    labels: None,
+                    langs: None,
+                    reply: Some(reminder.reply.clone()),
+                    tags: None,
+                    text: truncate_reply(
+                        &format!("reminder: {}", reminder.message),
+                        BLUESKY_MAX_GRAPHEMES,
+                    ),
+                };
+                if let Err(e) = create_post(&agent, record).await {
+                    reminder.attempts += 1;
+                    if reminder.attempts >= MAX_REMINDER_ATTEMPTS {
+                        error!(
+                            "Giving up on reminder {} after {} attempts: {}",
+                            reminder.id, reminder.attempts, e
+                        );
+                        continue;
+                    }
+                    error!(
+                        "Failed to post reminder {}, retrying later: {}",
+                        reminder.id, e
+                    );
+                    if let Err(e) = store.add(reminder).await {
+                        error!("Failed to requeue reminder: {}", e);
+                    }
+                }
+            }
+        }
+    });
+}
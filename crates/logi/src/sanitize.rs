@@ -0,0 +1,122 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use tracing::{info, warn};
+
+/// Pattern built from the wordlist at `OUTPUT_WORDLIST`, or `None` if no list is configured.
+static WORDLIST_RE: Lazy<Option<Regex>> = Lazy::new(|| {
+    let path = std::env::var("OUTPUT_WORDLIST").ok()?;
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            warn!("Could not read OUTPUT_WORDLIST {}: {}", path, e);
+            return None;
+        }
+    };
+    let words = parse_wordlist(&contents);
+    info!("Loaded {} filtered words from {}", words.len(), path);
+    wordlist_regex(&words)
+});
+
+/// Masks words from the configured wordlist in model output.
+///
+/// Returns the cleaned text and whether anything was masked. Without a wordlist the
+/// text is returned unchanged.
+pub fn sanitize_output(text: &str) -> (String, bool) {
+    match WORDLIST_RE.as_ref() {
+        Some(re) => mask(text, re),
+        None => (text.to_string(), false),
+    }
+}
+
+/// Like [`sanitize_output`], but against an explicit wordlist instead of `OUTPUT_WORDLIST`.
+pub fn sanitize_output_with(text: &str, words: &[String]) -> (String, bool) {
+    match wordlist_regex(words) {
+        Some(re) => mask(text, &re),
+        None => (text.to_string(), false),
+    }
+}
+
+/// One word or phrase per line; blank lines and `#` comments are ignored.
+fn parse_wordlist(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Case-insensitive, whole-word pattern matching any of `words`.
+fn wordlist_regex(words: &[String]) -> Option<Regex> {
+    let alternation = words
+        .iter()
+        .filter(|w| !w.trim().is_empty())
+        .map(|w| regex::escape(w.trim()))
+        .collect::<Vec<_>>()
+        .join("|");
+    if alternation.is_empty() {
+        return None;
+    }
+    match Regex::new(&format!(r"(?i)\b(?:{})\b", alternation)) {
+        Ok(re) => Some(re),
+        Err(e) => {
+            warn!("Could not build wordlist filter: {}", e);
+            None
+        }
+    }
+}
+
+fn mask(text: &str, re: &Regex) -> (String, bool) {
+    let filtered = re.is_match(text);
+    let cleaned = re
+        .replace_all(text, |caps: &regex::Captures| {
+            "*".repeat(caps[0].chars().count())
+        })
+        .into_owned();
+    (cleaned, filtered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn masks_listed_words() {
+        let (cleaned, filtered) = sanitize_output_with("that's a darn shame", &words(&["darn"]));
+        assert_eq!(cleaned, "that's a **** shame");
+        assert!(filtered);
+    }
+
+    #[test]
+    fn leaves_other_text_alone() {
+        let text = "darned if I know, darnation";
+        let (cleaned, filtered) = sanitize_output_with(text, &words(&["darn"]));
+        assert_eq!(cleaned, text);
+        assert!(!filtered);
+    }
+
+    #[test]
+    fn matches_regardless_of_case() {
+        let (cleaned, filtered) = sanitize_output_with("DARN it, Heck", &words(&["darn", "heck"]));
+        assert_eq!(cleaned, "**** it, ****");
+        assert!(filtered);
+    }
+
+    #[test]
+    fn empty_wordlist_filters_nothing() {
+        assert_eq!(
+            sanitize_output_with("darn", &words(&["", "  "])),
+            ("darn".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn wordlist_skips_blanks_and_comments() {
+        let parsed = parse_wordlist("# banned\ndarn\n\n  heck  \n");
+        assert_eq!(parsed, words(&["darn", "heck"]));
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::post::PostData;
+
+/// How long a fetched thread is reused before it's fetched again.
+pub const DEFAULT_THREAD_CACHE_TTL: Duration = Duration::from_secs(30);
+/// Threads kept at most; the oldest fetch is evicted first.
+pub const DEFAULT_MAX_CACHED_THREADS: usize = 256;
+
+/// Recently fetched threads, keyed on the URI of their root post, so rapid follow-ups
+/// in the same conversation don't refetch the whole thread.
+///
+/// An entry holds one chain of posts from the root down. Posts added on top of a
+/// fetch, such as the bot's own replies, keep the fetch's time, so nothing is served
+/// longer than the TTL after it was actually read from Bluesky.
+#[derive(Debug)]
+pub struct ThreadCache {
+    ttl: Duration,
+    capacity: usize,
+    threads: Mutex<HashMap<String, CachedThread>>,
+}
+
+#[derive(Debug)]
+struct CachedThread {
+    fetched_at: Instant,
+    posts: Vec<PostData>,
+}
+
+impl Default for ThreadCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_THREAD_CACHE_TTL, DEFAULT_MAX_CACHED_THREADS)
+    }
+}
+
+impl ThreadCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            threads: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The cached posts of the thread at `root_uri` from the root down to and including
+    /// `parent_uri`, if the thread was fetched recently and that post is in it.
+    pub fn chain_to(&self, root_uri: &str, parent_uri: &str) -> Option<Vec<PostData>> {
+        let threads = self.threads.lock().unwrap();
+        let thread = threads
+            .get(root_uri)
+            .filter(|thread| thread.fetched_at.elapsed() < self.ttl)?;
+        let end = thread
+            .posts
+            .iter()
+            .position(|post| post.uri == parent_uri)?;
+        Some(thread.posts[..=end].to_vec())
+    }
+
+    /// Stores a chain just fetched from Bluesky.
+    pub fn insert(&self, root_uri: &str, posts: Vec<PostData>) {
+        let mut threads = self.threads.lock().unwrap();
+        threads.retain(|_, thread| thread.fetched_at.elapsed() < self.ttl);
+        if threads.len() >= self.capacity && !threads.contains_key(root_uri) {
+            let oldest = threads
+                .iter()
+                .min_by_key(|(_, thread)| thread.fetched_at)
+                .map(|(uri, _)| uri.clone());
+            if let Some(oldest) = oldest {
+                threads.remove(&oldest);
+            }
+        }
+        threads.insert(
+            root_uri.to_string(),
+            CachedThread {
+                fetched_at: Instant::now(),
+                posts,
+            },
+        );
+    }
+
+    /// Replaces the chain of a cached thread with one built on top of it, keeping the
+    /// original fetch time. Does nothing if the thread isn't cached.
+    pub fn update(&self, root_uri: &str, posts: Vec<PostData>) {
+        if let Some(thread) = self.threads.lock().unwrap().get_mut(root_uri) {
+            thread.posts = posts;
+        }
+    }
+
+    /// Adds `post` to the end of the cached thread at `root_uri`, but only if the
+    /// chain ends with `parent_uri`, the post it replies to.
+    pub fn append(&self, root_uri: &str, parent_uri: &str, post: PostData) {
+        if let Some(thread) = self.threads.lock().unwrap().get_mut(root_uri) {
+            if thread
+                .posts
+                .last()
+                .is_some_and(|last| last.uri == parent_uri)
+            {
+                thread.posts.push(post);
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Error};
+use serde_json::{json, Value};
+use tracing::debug;
+
+use crate::tools::{get_public, AiTool, ToolContext, ToolError};
+
+pub const MAX_REPLY_IMAGES: usize = 4; // Bluesky allows four images per post
+const MAX_IMAGE_BYTES: usize = 1_000_000; // Bluesky rejects image blobs over 1 MB
+const MAX_ALT_CHARS: usize = 2000; // Long enough for a thorough description
+const SUPPORTED_TYPES: &[&str] = &["image/png", "image/jpeg", "image/gif", "image/webp"];
+
+/// An image downloaded for the reply that's being written, uploaded when it's posted.
+#[derive(Debug, Clone)]
+pub struct ReplyImage {
+    pub data: Vec<u8>,
+    pub mime: String,
+    pub alt: String,
+}
+
+tokio::task_local! {
+    /// Images attached to the reply being written, collected while tools run.
+    pub static REPLY_IMAGES: Arc<Mutex<Vec<ReplyImage>>>;
+}
+
+/// Tool that downloads an image and attaches it to the reply.
+pub struct AttachImageTool;
+
+#[async_trait::async_trait]
+impl AiTool for AttachImageTool {
+    fn name(&self) -> &str {
+        "attach_image"
+    }
+
+    fn description(&self) -> &str {
+        r#"Attaches an image from a URL to your reply, for example a picture a search turned up.
+Parameters:
+- `url`: The URL of the image (PNG, JPEG, GIF or WebP, at most 1 MB).
+- `alt`: Alt text describing the image for people who can't see it.
+At most 4 images can be attached to one reply.
+Example usage: { "url": "https://example.com/cat.jpg", "alt": "A grey cat asleep on a keyboard" }
+"#
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "url": { "type": "string", "description": "URL of a PNG, JPEG, GIF or WebP image" },
+                "alt": { "type": "string", "description": "Alt text describing the image" }
+            },
+            "required": ["url", "alt"]
+        })
+    }
+
+    async fn execute(&self, args: &Value, _ctx: &ToolContext<'_>) -> Result<Value, Error> {
+        let image_url = args
+            .get("url")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::InvalidArgs("Missing 'url' parameter".to_string()))?;
+        let alt = args
+            .get("alt")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|a| !a.is_empty())
+            .ok_or_else(|| ToolError::InvalidArgs("Missing 'alt' parameter".to_string()))?;
+        if alt.chars().count() > MAX_ALT_CHARS {
+            return Err(ToolError::InvalidArgs(format!(
+                "'alt' must be at most {} characters",
+                MAX_ALT_CHARS
+            ))
+            .into());
+        }
+
+        let images = REPLY_IMAGES
+            .try_with(|images| images.clone())
+            .map_err(|_| anyhow!("Images can only be attached when replying to a post"))?;
+        if images.lock().unwrap().len() >= MAX_REPLY_IMAGES {
+            return Err(anyhow!(
+                "This reply already has {} images, the most a post can hold",
+                MAX_REPLY_IMAGES
+            ));
+        }
+
+        let resp = get_public(image_url)
+            .await
+            .map_err(|e| anyhow!("Could not download image: {}", e))?;
+        if !resp.status().is_success() {
+            return Err(anyhow!("Could not download image: HTTP {}", resp.status()));
+        }
+
+        let mime = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(';').next())
+            .map(|v| v.trim().to_ascii_lowercase())
+            .unwrap_or_default();
+        if !SUPPORTED_TYPES.contains(&mime.as_str()) {
+            return Err(anyhow!(
+                "Unsupported image format '{}', expected PNG, JPEG, GIF or WebP",
+                mime
+            ));
+        }
+        if resp
+            .content_length()
+            .is_some_and(|len| len as usize > MAX_IMAGE_BYTES)
+        {
+            return Err(anyhow!(
+                "Image is too large, the limit is {} bytes",
+                MAX_IMAGE_BYTES
+            ));
+        }
+
+        let data = resp
+            .bytes()
+            .await
+            .map_err(|e| anyhow!("Could not download image: {}", e))?;
+        if data.len() > MAX_IMAGE_BYTES {
+            return Err(anyhow!(
+                "Image is too large, the limit is {} bytes",
+                MAX_IMAGE_BYTES
+            ));
+        }
+        debug!("Attaching {} byte {} image", data.len(), mime);
+
+        let mut images = images.lock().unwrap();
+        images.push(ReplyImage {
+            data: data.to_vec(),
+            mime,
+            alt: alt.to_string(),
+        });
+        Ok(json!({ "attached": true, "images_in_reply": images.len() }))
+    }
+}
@@ -1,8 +1,12 @@
 use anyhow::{anyhow, Error};
+use calc::ast::parser::ExprParser;
+use calc::ast::{Constant, Expr, Function, InfixOperator, PrefixOperator, Term};
 use calc::Context;
-use serde_json::Value;
+use serde_json::{json, Value};
 
-use crate::tools::AiTool;
+use crate::tools::{AiTool, ToolContext, ToolError};
+
+const MAX_EXPLAIN_STEPS: usize = 20; // Longer breakdowns stop being readable in a reply
 
 /// Example tool that performs basic math operations.
 pub struct MathTool;
@@ -45,23 +49,172 @@ functions:
 constants:
     e pi π
 
+Set `explain` to true to also get the steps in the order they were evaluated, with intermediate values.
+
 Example usage:
 tool_name: calculator
-tool_args: { "expr": "round(12345 / 543)" }
+tool_args: { "expr": "round(12345 / 543)", "explain": false }
 "#
     }
 
-    async fn execute(&self, args: &Value) -> Result<Value, Error> {
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "expr": { "type": "string", "description": "The expression to evaluate" },
+                "explain": { "type": "boolean", "description": "Also return the evaluation steps" }
+            },
+            "required": ["expr"]
+        })
+    }
+
+    async fn execute(&self, args: &Value, _ctx: &ToolContext<'_>) -> Result<Value, Error> {
         let expr = args
             .get("expr")
             .and_then(|v| v.as_str())
-            .ok_or_else(|| anyhow!("Missing 'expr' parameter"))?;
+            .ok_or_else(|| ToolError::InvalidArgs("Missing 'expr' parameter".to_string()))?;
+
+        let explain = args
+            .get("explain")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
 
         let mut ctx: Context<f64> = Context::default();
 
-        match ctx.evaluate(expr) {
-            Ok(result) => Ok(serde_json::json!({ "result": result })),
-            Err(e) => Err(anyhow!("Error evaluating expression '{}': {}", expr, e)),
+        let result = ctx.evaluate(expr).map_err(|e| {
+            ToolError::InvalidArgs(format!("Error evaluating expression '{}': {}", expr, e))
+        })?;
+        if !explain {
+            return Ok(serde_json::json!({ "result": result }));
+        }
+
+        match explain_steps(expr) {
+            Some(steps) if steps.len() > MAX_EXPLAIN_STEPS => Ok(serde_json::json!({
+                "result": result,
+                "steps": steps[..MAX_EXPLAIN_STEPS],
+                "note": format!("Only the first {} steps are shown", MAX_EXPLAIN_STEPS),
+            })),
+            Some(steps) => Ok(serde_json::json!({ "result": result, "steps": steps })),
+            None => Ok(serde_json::json!({
+                "result": result,
+                "note": "This expression couldn't be broken down into steps",
+            })),
+        }
+    }
+}
+
+/// Breaks `expr` into one step per operator or function, innermost first, e.g.
+/// `12345 / 543 = 22.73…` then `round(22.73…) = 23`. Returns `None` for anything that
+/// can't be re-evaluated piecewise, such as history lookups.
+fn explain_steps(expr: &str) -> Option<Vec<String>> {
+    let parsed = ExprParser::new().parse(expr).ok()?;
+    let mut steps = Vec::new();
+    explain_expr(&parsed, &mut steps)?;
+    Some(steps)
+}
+
+/// Evaluates `expr` bottom-up, recording a step for each operation with its operands
+/// already reduced to values.
+fn explain_expr(expr: &Expr, steps: &mut Vec<String>) -> Option<f64> {
+    // Each step is evaluated with its operands parenthesised, so negative values parse,
+    // and shown without them
+    let (source, shown) = match expr {
+        Expr::Term(term) => return evaluate(&term_source(term)?),
+        Expr::Group(inner) => return explain_expr(inner, steps),
+        Expr::Prefix(op, operand) => {
+            let value = explain_expr(operand, steps)?;
+            let symbol = match op {
+                PrefixOperator::Negation => "-",
+                PrefixOperator::Not => "!",
+            };
+            (
+                format!("{}({})", symbol, value),
+                format!("{}{}", symbol, value),
+            )
+        }
+        Expr::Infix(left, op, right) => {
+            let left = explain_expr(left, steps)?;
+            let right = explain_expr(right, steps)?;
+            let symbol = infix_symbol(*op);
+            (
+                format!("({}) {} ({})", left, symbol, right),
+                format!("{} {} {}", left, symbol, right),
+            )
+        }
+        Expr::Func(func, operand) => {
+            let value = explain_expr(operand, steps)?;
+            let call = format!("{}({})", function_name(*func), value);
+            (call.clone(), call)
+        }
+    };
+
+    let value = evaluate(&source)?;
+    // Negating a literal isn't worth a step of its own
+    if shown != value.to_string() {
+        steps.push(format!("{} = {}", shown, value));
+    }
+    Some(value)
+}
+
+fn evaluate(expr: &str) -> Option<f64> {
+    Context::<f64>::default().evaluate(expr).ok()
+}
+
+fn term_source(term: &Term) -> Option<String> {
+    match term {
+        Term::Literal(s) | Term::HexLiteral(s) | Term::OctLiteral(s) | Term::BinLiteral(s) => {
+            Some(s.to_string())
         }
+        Term::Constant(Constant::E) => Some("e".to_string()),
+        Term::Constant(Constant::Pi) => Some("pi".to_string()),
+        Term::History(..) => None,
+    }
+}
+
+fn infix_symbol(op: InfixOperator) -> &'static str {
+    match op {
+        InfixOperator::Add => "+",
+        InfixOperator::Sub => "-",
+        InfixOperator::Mul => "*",
+        InfixOperator::Div => "/",
+        InfixOperator::TruncDiv => "//",
+        InfixOperator::Pow => "**",
+        InfixOperator::Rem => "%",
+        InfixOperator::Lshift => "<<",
+        InfixOperator::Rshift => ">>",
+        InfixOperator::RotateL => "<<<",
+        InfixOperator::RotateR => ">>>",
+        InfixOperator::BitAnd => "&",
+        InfixOperator::BitOr => "|",
+        InfixOperator::BitXor => "^",
+    }
+}
+
+fn function_name(func: Function) -> &'static str {
+    match func {
+        Function::Abs => "abs",
+        Function::Ceil => "ceil",
+        Function::Floor => "floor",
+        Function::Round => "round",
+        Function::Sin => "sin",
+        Function::Cos => "cos",
+        Function::Tan => "tan",
+        Function::Sinh => "sinh",
+        Function::Cosh => "cosh",
+        Function::Tanh => "tanh",
+        Function::Asin => "asin",
+        Function::Acos => "acos",
+        Function::Atan => "atan",
+        Function::Asinh => "asinh",
+        Function::Acosh => "acosh",
+        Function::Atanh => "atanh",
+        Function::Rad => "rad",
+        Function::Deg => "deg",
+        Function::Sqrt => "sqrt",
+        Function::Cbrt => "cbrt",
+        Function::Log => "log",
+        Function::Lg => "lg",
+        Function::Ln => "ln",
+        Function::Exp => "exp",
     }
 }
@@ -0,0 +1,121 @@
+use anyhow::{anyhow, Error};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::debug;
+
+use crate::tools::{http_client, AiTool, ToolContext, ToolError};
+
+const DICTIONARY_API: &str = "https://api.dictionaryapi.dev/api/v2/entries/en/";
+const MAX_DEFINITIONS: usize = 3; // Per part of speech, to keep tool results short
+
+/// A single entry returned by dictionaryapi.dev.
+#[derive(Deserialize)]
+struct Entry {
+    word: String,
+    #[serde(default)]
+    meanings: Vec<Meaning>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Meaning {
+    part_of_speech: String,
+    #[serde(default)]
+    definitions: Vec<Definition>,
+}
+
+#[derive(Deserialize)]
+struct Definition {
+    definition: String,
+    example: Option<String>,
+}
+
+/// Tool that looks up English word definitions.
+pub struct DictionaryTool;
+
+#[async_trait::async_trait]
+impl AiTool for DictionaryTool {
+    fn name(&self) -> &str {
+        "dictionary"
+    }
+
+    fn description(&self) -> &str {
+        r#"Looks up the definition of an English word. Use this when asked what a word means instead of guessing.
+Parameters:
+- `word`: The word to define.
+Returns the part of speech, definitions, and an example sentence where available.
+Example usage: { "word": "ephemeral" }
+"#
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "word": { "type": "string", "description": "The word to define" }
+            },
+            "required": ["word"]
+        })
+    }
+
+    async fn execute(&self, args: &Value, _ctx: &ToolContext<'_>) -> Result<Value, Error> {
+        let word = args
+            .get("word")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|w| !w.is_empty())
+            .ok_or_else(|| ToolError::InvalidArgs("Missing 'word' parameter".to_string()))?;
+
+        let mut url = reqwest::Url::parse(DICTIONARY_API)?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("Invalid dictionary API URL"))?
+            .pop_if_empty()
+            .push(word);
+
+        let resp = http_client()
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| anyhow!("Request error: {}", e))?;
+
+        debug!("Response status: {}", resp.status());
+
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(json!({
+                "word": word,
+                "found": false,
+                "message": format!("No definitions found for '{}'", word),
+            }));
+        }
+
+        let entries: Vec<Entry> = resp
+            .error_for_status()
+            .map_err(|e| anyhow!("Dictionary API error: {}", e))?
+            .json()
+            .await
+            .map_err(|e| anyhow!("Body error: {}", e))?;
+
+        let meanings: Vec<Value> = entries
+            .iter()
+            .flat_map(|entry| &entry.meanings)
+            .map(|meaning| {
+                let definitions: Vec<Value> = meaning
+                    .definitions
+                    .iter()
+                    .take(MAX_DEFINITIONS)
+                    .map(|d| json!({ "definition": d.definition, "example": d.example }))
+                    .collect();
+                json!({
+                    "part_of_speech": meaning.part_of_speech,
+                    "definitions": definitions,
+                })
+            })
+            .collect();
+
+        Ok(json!({
+            "word": entries.first().map(|e| e.word.as_str()).unwrap_or(word),
+            "found": true,
+            "meanings": meanings,
+        }))
+    }
+}
@@ -0,0 +1,199 @@
+use anyhow::{anyhow, Error};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::debug;
+
+use crate::tools::{http_client, AiTool, ToolContext, ToolError};
+
+const GITHUB_API: &str = "https://api.github.com";
+
+#[derive(Deserialize)]
+struct Repo {
+    full_name: String,
+    description: Option<String>,
+    html_url: String,
+    stargazers_count: u64,
+    forks_count: u64,
+    open_issues_count: u64,
+    language: Option<String>,
+    archived: bool,
+    pushed_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    name: Option<String>,
+    published_at: Option<String>,
+    html_url: String,
+}
+
+/// Tool that looks up a GitHub repository.
+pub struct GithubTool {
+    token: Option<String>,
+}
+
+impl GithubTool {
+    pub fn new(token: Option<String>) -> Self {
+        Self { token }
+    }
+
+    /// Reads `GITHUB_TOKEN`, which raises the unauthenticated limit of 60 requests an hour.
+    pub fn from_env() -> Self {
+        Self::new(std::env::var("GITHUB_TOKEN").ok().filter(|t| !t.is_empty()))
+    }
+
+    fn get(&self, path: &str) -> RequestBuilder {
+        let request = http_client()
+            .get(format!("{}{}", GITHUB_API, path))
+            .header("Accept", "application/vnd.github+json")
+            .header("X-GitHub-Api-Version", "2022-11-28");
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+}
+
+/// Accepts `owner/name` or a github.com URL and returns `(owner, name)`.
+fn parse_repo(repo: &str) -> Option<(&str, &str)> {
+    let repo = repo.trim();
+    let repo = repo
+        .strip_prefix("https://")
+        .or_else(|| repo.strip_prefix("http://"))
+        .unwrap_or(repo);
+    let repo = repo.strip_prefix("github.com/").unwrap_or(repo);
+    let repo = repo.trim_end_matches('/');
+    let repo = repo.strip_suffix(".git").unwrap_or(repo);
+
+    let (owner, name) = repo.split_once('/')?;
+    let valid = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    };
+    (valid(owner) && valid(name)).then_some((owner, name))
+}
+
+/// Turns GitHub's rate-limit responses into an error saying so, rather than a bare 403.
+fn check_rate_limit(resp: &Response) -> Result<(), Error> {
+    let status = resp.status();
+    let exhausted = resp
+        .headers()
+        .get("x-ratelimit-remaining")
+        .and_then(|v| v.to_str().ok())
+        == Some("0");
+    if status == StatusCode::TOO_MANY_REQUESTS || (status == StatusCode::FORBIDDEN && exhausted) {
+        let reset = resp
+            .headers()
+            .get("x-ratelimit-reset")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<i64>().ok())
+            .and_then(|at| time::OffsetDateTime::from_unix_timestamp(at).ok())
+            .and_then(|at| {
+                at.format(&time::format_description::well_known::Rfc3339)
+                    .ok()
+            });
+        return Err(match reset {
+            Some(reset) => anyhow!("GitHub rate limit reached, resets at {}", reset),
+            None => anyhow!("GitHub rate limit reached, try again later"),
+        });
+    }
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl AiTool for GithubTool {
+    fn name(&self) -> &str {
+        "github"
+    }
+
+    fn description(&self) -> &str {
+        r#"Looks up a GitHub repository.
+Parameters:
+- `repo`: The repository as "owner/name", or its github.com URL.
+Returns the description, stars, forks, main language, open issues and the latest release.
+Example usage: { "repo": "rust-lang/rust" }
+"#
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "repo": { "type": "string", "description": "\"owner/name\" or a github.com URL" }
+            },
+            "required": ["repo"]
+        })
+    }
+
+    async fn execute(&self, args: &Value, _ctx: &ToolContext<'_>) -> Result<Value, Error> {
+        let repo = args
+            .get("repo")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::InvalidArgs("Missing 'repo' parameter".to_string()))?;
+        let (owner, name) = parse_repo(repo).ok_or_else(|| {
+            ToolError::InvalidArgs(format!("'{}' is not an owner/name repository", repo))
+        })?;
+
+        let resp = self
+            .get(&format!("/repos/{}/{}", owner, name))
+            .send()
+            .await
+            .map_err(|e| anyhow!("Request error: {}", e))?;
+
+        debug!("Response status: {}", resp.status());
+        check_rate_limit(&resp)?;
+
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(json!({
+                "repo": format!("{}/{}", owner, name),
+                "found": false,
+                "message": format!("No public repository named {}/{}", owner, name),
+            }));
+        }
+
+        let info: Repo = resp
+            .error_for_status()
+            .map_err(|e| anyhow!("GitHub API error: {}", e))?
+            .json()
+            .await
+            .map_err(|e| anyhow!("Body error: {}", e))?;
+
+        // Repositories without releases 404 here, which just means there's none
+        let resp = self
+            .get(&format!("/repos/{}/{}/releases/latest", owner, name))
+            .send()
+            .await
+            .map_err(|e| anyhow!("Request error: {}", e))?;
+        check_rate_limit(&resp)?;
+        let latest_release = if resp.status().is_success() {
+            resp.json::<Release>().await.ok().map(|release| {
+                json!({
+                    "tag": release.tag_name,
+                    "name": release.name,
+                    "published_at": release.published_at,
+                    "url": release.html_url,
+                })
+            })
+        } else {
+            None
+        };
+
+        Ok(json!({
+            "repo": info.full_name,
+            "found": true,
+            "description": info.description,
+            "url": info.html_url,
+            "stars": info.stargazers_count,
+            "forks": info.forks_count,
+            "open_issues": info.open_issues_count,
+            "language": info.language,
+            "archived": info.archived,
+            "last_push": info.pushed_at,
+            "latest_release": latest_release,
+        }))
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Error};
+use atrium_api::com::atproto::identity::resolve_identity;
+use atrium_api::did_doc::DidDocument;
+use atrium_api::types::string::AtIdentifier;
+use atrium_api::xrpc::error::{XrpcError, XrpcErrorKind};
+use bsky_sdk::BskyAgent;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tracing::debug;
+
+use crate::tools::{AiTool, ToolContext, ToolError};
+
+const IDENTITY_CACHE_TTL: Duration = Duration::from_secs(600); // Handles rarely change, but they can
+const INVALID_HANDLE: &str = "handle.invalid"; // What the PDS reports when a handle doesn't verify
+
+/// An account's DID together with its verified handle and PDS.
+#[derive(Debug, Clone, Serialize)]
+pub struct Identity {
+    pub did: String,
+    /// `None` when the handle doesn't point back to the DID.
+    pub handle: Option<String>,
+    pub pds: Option<String>,
+}
+
+/// Tool that resolves a Bluesky handle to its DID, or a DID to its handle, along with
+/// the account's PDS. Needs the agent from the [`ToolContext`].
+#[derive(Default)]
+pub struct IdentityTool {
+    cache: Mutex<HashMap<String, (Instant, Identity)>>,
+}
+
+impl IdentityTool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolves a handle or DID, using a cached result when there's a recent one.
+    /// Returns `None` if the handle or DID doesn't exist.
+    pub async fn resolve(&self, agent: &BskyAgent, actor: &str) -> Result<Option<Identity>, Error> {
+        let key = normalize_actor(actor);
+        if let Some(identity) = self.cached(&key) {
+            debug!("Identity cache hit for {}", key);
+            return Ok(Some(identity));
+        }
+        let identifier: AtIdentifier = key
+            .parse()
+            .map_err(|_| ToolError::InvalidArgs(format!("'{}' is not a handle or DID", actor)))?;
+
+        let info = match agent
+            .api
+            .com
+            .atproto
+            .identity
+            .resolve_identity(resolve_identity::ParametersData { identifier }.into())
+            .await
+        {
+            Ok(info) => info,
+            Err(atrium_api::xrpc::Error::XrpcResponse(XrpcError {
+                error: Some(XrpcErrorKind::Custom(e)),
+                ..
+            })) => {
+                debug!("Could not resolve {}: {}", key, e);
+                return Ok(None);
+            }
+            Err(e) => return Err(anyhow!("Identity resolution error: {}", e)),
+        };
+
+        let pds = serde_json::to_value(&info.did_doc)
+            .ok()
+            .and_then(|doc| serde_json::from_value::<DidDocument>(doc).ok())
+            .and_then(|doc| doc.get_pds_endpoint());
+        let handle = Some(info.handle.to_string()).filter(|handle| handle != INVALID_HANDLE);
+        let identity = Identity {
+            did: info.did.to_string(),
+            handle,
+            pds,
+        };
+        self.store(&key, &identity);
+        Ok(Some(identity))
+    }
+
+    fn cached(&self, key: &str) -> Option<Identity> {
+        let cache = self.cache.lock().unwrap();
+        cache
+            .get(key)
+            .filter(|(at, _)| at.elapsed() < IDENTITY_CACHE_TTL)
+            .map(|(_, identity)| identity.clone())
+    }
+
+    /// Caches `identity` under the identifier it was looked up by, and under its DID and
+    /// handle, so resolving the other way round is a hit too.
+    fn store(&self, key: &str, identity: &Identity) {
+        let mut cache = self.cache.lock().unwrap();
+        cache.retain(|_, (at, _)| at.elapsed() < IDENTITY_CACHE_TTL);
+        let now = Instant::now();
+        let keys = [
+            Some(key),
+            Some(identity.did.as_str()),
+            identity.handle.as_deref(),
+        ];
+        for key in keys.into_iter().flatten() {
+            cache.insert(normalize_actor(key), (now, identity.clone()));
+        }
+    }
+}
+
+/// Handles are case-insensitive and often written with a leading `@`.
+fn normalize_actor(actor: &str) -> String {
+    let actor = actor.trim().trim_start_matches('@');
+    if actor.starts_with("did:") {
+        actor.to_string()
+    } else {
+        actor.to_lowercase()
+    }
+}
+
+#[async_trait::async_trait]
+impl AiTool for IdentityTool {
+    fn name(&self) -> &str {
+        "resolve_identity"
+    }
+
+    fn description(&self) -> &str {
+        r#"Resolves a Bluesky handle to its DID, or a DID to its handle.
+Parameters:
+- `actor`: A handle like "alice.bsky.social" or a DID like "did:plc:...".
+Returns the DID, the verified handle (null if it doesn't verify) and the URL of the account's PDS.
+Example usage: { "actor": "bsky.app" }
+"#
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "actor": { "type": "string", "description": "A handle or DID" }
+            },
+            "required": ["actor"]
+        })
+    }
+
+    async fn execute(&self, args: &Value, ctx: &ToolContext<'_>) -> Result<Value, Error> {
+        let actor = args
+            .get("actor")
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.trim().is_empty())
+            .ok_or_else(|| ToolError::InvalidArgs("Missing 'actor' parameter".to_string()))?;
+        let agent = ctx
+            .agent
+            .ok_or_else(|| anyhow!("Resolving identities needs a Bluesky session"))?;
+
+        match self.resolve(agent, actor).await? {
+            Some(identity) => Ok(json!({
+                "found": true,
+                "did": identity.did,
+                "handle": identity.handle,
+                "pds": identity.pds,
+            })),
+            None => Ok(json!({
+                "found": false,
+                "message": format!("No Bluesky account found for {}", actor),
+            })),
+        }
+    }
+}
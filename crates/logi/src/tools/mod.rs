@@ -1,17 +1,49 @@
 use anyhow::{anyhow, Error};
+use bsky_sdk::BskyAgent;
+use once_cell::sync::Lazy;
 use regex::Regex;
+use reqwest::Url;
 use serde_json::Value;
-use tracing::info;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tracing::{debug, info, warn, Instrument};
 
+pub mod attach_image;
 pub mod calc;
+pub mod dictionary;
+pub mod github;
+pub mod identity;
+pub mod ocr;
+pub mod price;
+pub mod remember;
+pub mod reminder;
 pub mod search;
+pub mod summarize;
+pub mod translate;
 pub mod website;
 
 #[async_trait::async_trait]
 pub trait AiTool: Send + Sync {
     fn name(&self) -> &str;
     fn description(&self) -> &str;
-    async fn execute(&self, args: &Value) -> anyhow::Result<Value>;
+    /// JSON Schema of the arguments object. Defaults to an object with any properties.
+    fn parameters(&self) -> Value {
+        serde_json::json!({ "type": "object" })
+    }
+    async fn execute(&self, args: &Value, ctx: &ToolContext<'_>) -> anyhow::Result<Value>;
+}
+
+/// Handles and settings a tool may need besides its arguments, passed to every call.
+///
+/// Not every caller has all of them, e.g. the CLI has no Bluesky session, so tools
+/// that need one should fail with an explanation when it's `None`.
+#[derive(Clone, Copy, Default)]
+pub struct ToolContext<'a> {
+    /// The logged-in Bluesky session.
+    pub agent: Option<&'a BskyAgent>,
+    /// Tools with lasting side effects, like storing a fact or scheduling a reminder,
+    /// report what they would have done instead of doing it.
+    pub dry_run: bool,
 }
 
 /// Represents a parsed tool call from an LLM response.
@@ -22,24 +54,369 @@ pub struct ToolCall {
     pub tool_args: serde_json::Value,
 }
 
+/// Default number of times the same tool call may repeat before a loop is broken.
+pub const DEFAULT_MAX_TOOL_REPEATS: usize = 3;
+/// Default number of consecutive iterations calling unknown tools before a loop is broken.
+pub const DEFAULT_MAX_TOOL_NOT_FOUND: usize = 2;
+/// Default cap on the total number of tool-call iterations in one loop, repeated or not.
+pub const DEFAULT_MAX_TOOL_ITERATIONS: usize = 10;
+/// How long a single tool call may run before it is abandoned.
+pub const TOOL_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Why a tool call failed.
+///
+/// Tools return `anyhow::Error`; returning a `ToolError` through it (usually
+/// `InvalidArgs`) keeps the variant, and anything else becomes `Execution`.
+#[derive(Debug)]
+pub enum ToolError {
+    /// The model called a tool that doesn't exist. Holds the available tool names.
+    NotFound {
+        name: String,
+        available: Vec<String>,
+    },
+    /// The arguments were missing or wrong; calling again the same way won't help.
+    InvalidArgs(String),
+    /// The tool ran and failed, e.g. on a network error.
+    Execution(anyhow::Error),
+    /// The tool didn't finish within [`TOOL_TIMEOUT`].
+    Timeout,
+}
+
+impl std::fmt::Display for ToolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ToolError::NotFound { name, available } => write!(
+                f,
+                "Tool not found: `{}`. Available tools: {}",
+                name,
+                available.join(", ")
+            ),
+            ToolError::InvalidArgs(message) => write!(f, "Invalid arguments: {}", message),
+            ToolError::Execution(e) => write!(f, "{}", e),
+            ToolError::Timeout => write!(f, "Timed out after {} seconds", TOOL_TIMEOUT.as_secs()),
+        }
+    }
+}
+
+impl std::error::Error for ToolError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ToolError::Execution(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<anyhow::Error> for ToolError {
+    fn from(e: anyhow::Error) -> Self {
+        e.downcast::<ToolError>()
+            .unwrap_or_else(ToolError::Execution)
+    }
+}
+
+/// User-agent sent by tools when `AIGIS_USER_AGENT` isn't set. DuckDuckGo's HTML
+/// endpoint rejects obviously non-browser agents.
+pub const DEFAULT_USER_AGENT: &str =
+    "Mozilla/5.0 (Macintosh; Intel Mac OS X 10.15; rv:140.0) Gecko/20100101 Firefox/140.0";
+
+const MAX_PUBLIC_REDIRECTS: usize = 5; // Enough for http -> https plus a link shortener or two
+
+static HTTP_CLIENT: Lazy<reqwest::Client> = Lazy::new(build_http_client);
+
+/// Shared HTTP client for outbound tool requests.
+///
+/// Sends `AIGIS_USER_AGENT` (or [`DEFAULT_USER_AGENT`]). Proxies come from reqwest's
+/// own handling of `HTTP_PROXY`/`HTTPS_PROXY`, which also honours `NO_PROXY`.
+pub fn http_client() -> &'static reqwest::Client {
+    &HTTP_CLIENT
+}
+
+fn client_builder() -> reqwest::ClientBuilder {
+    let user_agent =
+        std::env::var("AIGIS_USER_AGENT").unwrap_or_else(|_| DEFAULT_USER_AGENT.to_string());
+    reqwest::Client::builder().user_agent(user_agent)
+}
+
+fn build_http_client() -> reqwest::Client {
+    client_builder().build().unwrap_or_else(|e| {
+        warn!("Failed to build HTTP client, using defaults: {}", e);
+        reqwest::Client::new()
+    })
+}
+
+/// GETs a URL a tool was asked to fetch, refusing anything that isn't public http(s).
+///
+/// Redirects are followed here rather than by reqwest, so every hop goes through
+/// [`resolve_public_url`], and each request is sent by a client pinned to the addresses
+/// that were checked, so the host can't resolve somewhere else by the time it connects.
+/// Behind an `HTTP_PROXY`/`HTTPS_PROXY` the proxy makes the connection, so it has to
+/// refuse internal addresses itself.
+pub(crate) async fn get_public(url: &str) -> anyhow::Result<reqwest::Response> {
+    let mut url = url.to_string();
+    for _ in 0..=MAX_PUBLIC_REDIRECTS {
+        let (parsed, addrs) = resolve_public_url(&url).await?;
+        let host = parsed.host_str().unwrap_or_default();
+        let client = client_builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .resolve_to_addrs(host.trim_start_matches('[').trim_end_matches(']'), &addrs)
+            .build()
+            .map_err(|e| anyhow!("Failed to build HTTP client: {}", e))?;
+        let resp = client
+            .get(parsed.clone())
+            .send()
+            .await
+            .map_err(|e| anyhow!("Request error: {}", e))?;
+
+        let location = resp
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|v| v.to_str().ok());
+        let (true, Some(location)) = (resp.status().is_redirection(), location) else {
+            return Ok(resp);
+        };
+        url = parsed
+            .join(location)
+            .map_err(|e| anyhow!("Invalid redirect to '{}': {}", location, e))?
+            .to_string();
+        debug!("Following redirect to {}", url);
+    }
+    Err(anyhow!("Too many redirects"))
+}
+
+/// Parses a URL a tool was asked to fetch and rejects anything that isn't public
+/// http(s): other schemes, and hosts that resolve to loopback, private, link-local or
+/// otherwise internal addresses. Returns the URL with the addresses that were checked.
+async fn resolve_public_url(url: &str) -> anyhow::Result<(Url, Vec<SocketAddr>)> {
+    let parsed = Url::parse(url).map_err(|e| anyhow!("Invalid URL '{}': {}", url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(anyhow!("Only http and https URLs are allowed"));
+    }
+    let host = parsed
+        .host_str()
+        .ok_or_else(|| anyhow!("URL '{}' has no host", url))?;
+    let port = parsed.port_or_known_default().unwrap_or(80);
+    // Bracketed IPv6 literals resolve fine once the brackets are gone
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| anyhow!("Could not resolve '{}': {}", host, e))?
+        .collect();
+    if addrs.iter().any(|addr| !is_public_ip(addr.ip())) {
+        return Err(anyhow!("URL '{}' points to a non-public address", url));
+    }
+    Ok((parsed, addrs))
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            !(v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_broadcast()
+                || v4.is_unspecified()
+                || v4.is_multicast()
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (v4.octets()[0] == 100 && (v4.octets()[1] & 0xc0) == 64))
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(v4));
+            }
+            let first = v6.segments()[0];
+            !(v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                // Unique local, fc00::/7
+                || (first & 0xfe00) == 0xfc00
+                // Link-local, fe80::/10
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+// Regex for the new special format
+static TOOL_CALL_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"<\u{FF5C}tool▁call▁begin\u{FF5C}>(?P<type>\w+)<\u{FF5C}tool▁sep\u{FF5C}>(?P<name>\w+)\s*```json\s*(?P<args>\{.*?\})\s*```<\u{FF5C}tool▁call▁end\u{FF5C}>"
+    ).unwrap()
+});
+// Regex for the format without tags: function function_name ```json {...} ```
+static TOOL_CALL_UNTAGGED_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"function\s+(?P<name>\w+)\s*```json\s*(?P<args>\{.*?\})\s*```").unwrap()
+});
+const TOOL_CALLS_END: &str = "<\u{FF5C}tool▁calls▁end\u{FF5C}>";
+// Regex for the wrapper tokens around a batch of tool calls
+static TOOL_CALLS_WRAPPER_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"<\u{FF5C}tool▁calls▁(begin|end)\u{FF5C}>").unwrap());
+
+/// Guards a tool-call loop against the model repeating the same call forever.
+///
+/// Call [`ToolLoopGuard::check`] once per loop iteration with that iteration's
+/// tool calls; it returns `false` once the same batch of calls has been seen more
+/// than `max_repeats` times in a row. Batches are compared as a whole and ignore
+/// ordering, so a model alternating `A, B` and `B, A` still trips the guard.
+///
+/// Independently of repetition, `check` also returns `false` once more than
+/// `max_iterations` batches have been seen, so a long chain of distinct calls can't
+/// run forever either; [`ToolLoopGuard::hit_iteration_cap`] tells the two apart.
+///
+/// Call [`ToolLoopGuard::check_results`] with each iteration's calls and results; it
+/// returns `false` once the model has kept calling tools that don't exist, or has
+/// repeated a call that already failed with [`ToolError::InvalidArgs`], so the caller
+/// can fall back to a plain-text answer instead of burning the rest of the loop.
+#[derive(Debug, Clone)]
+pub struct ToolLoopGuard {
+    max_repeats: usize,
+    max_not_found: usize,
+    max_iterations: usize,
+    iterations: usize,
+    last_calls: Option<Vec<(String, Value)>>,
+    repeats: usize,
+    not_found: usize,
+    invalid_calls: Vec<(String, Value)>,
+}
+
+impl ToolLoopGuard {
+    pub fn new(max_repeats: usize) -> Self {
+        Self {
+            max_repeats,
+            max_not_found: DEFAULT_MAX_TOOL_NOT_FOUND,
+            max_iterations: DEFAULT_MAX_TOOL_ITERATIONS,
+            iterations: 0,
+            last_calls: None,
+            repeats: 0,
+            not_found: 0,
+            invalid_calls: Vec::new(),
+        }
+    }
+
+    /// Builds a guard from `MAX_TOOL_REPEATS` and `MAX_TOOL_ITERATIONS`, falling back
+    /// to the defaults when they are unset or invalid.
+    pub fn from_env() -> Self {
+        let max_repeats = env_limit("MAX_TOOL_REPEATS").unwrap_or(DEFAULT_MAX_TOOL_REPEATS);
+        let max_iterations =
+            env_limit("MAX_TOOL_ITERATIONS").unwrap_or(DEFAULT_MAX_TOOL_ITERATIONS);
+        Self::new(max_repeats).with_max_iterations(max_iterations)
+    }
+
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// How many rounds of tool calls [`ToolLoopGuard::check`] has seen.
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    /// Whether the last failed [`ToolLoopGuard::check`] was due to the iteration cap
+    /// rather than a repeated call.
+    pub fn hit_iteration_cap(&self) -> bool {
+        self.iterations > self.max_iterations
+    }
+
+    /// Records this iteration's tool results and returns whether the loop may continue.
+    /// `results` must be in the same order as `tool_calls`, as [`execute_tool_calls`]
+    /// returns them.
+    pub fn check_results(
+        &mut self,
+        tool_calls: &[ToolCall],
+        results: &[(String, Result<Value, ToolError>)],
+    ) -> bool {
+        let any_not_found = results
+            .iter()
+            .any(|(_, result)| matches!(result, Err(ToolError::NotFound { .. })));
+        if any_not_found {
+            self.not_found += 1;
+        } else {
+            self.not_found = 0;
+        }
+
+        let mut repeated_invalid = false;
+        for (call, (_, result)) in tool_calls.iter().zip(results) {
+            if matches!(result, Err(ToolError::InvalidArgs(_))) {
+                let signature = (call.tool_name.clone(), call.tool_args.clone());
+                if self.invalid_calls.contains(&signature) {
+                    repeated_invalid = true;
+                } else {
+                    self.invalid_calls.push(signature);
+                }
+            }
+        }
+
+        self.not_found < self.max_not_found && !repeated_invalid
+    }
+
+    /// Records this iteration's tool calls and returns whether the loop may continue.
+    pub fn check(&mut self, tool_calls: &[ToolCall]) -> bool {
+        if tool_calls.is_empty() {
+            return true;
+        }
+        self.iterations += 1;
+        if self.hit_iteration_cap() {
+            return false;
+        }
+        let calls = Self::signature(tool_calls);
+
+        if self.last_calls.as_ref() == Some(&calls) {
+            if self.repeats >= self.max_repeats {
+                return false;
+            }
+            self.repeats += 1;
+        } else {
+            self.repeats = 1; // Reset count for new tool calls
+        }
+        self.last_calls = Some(calls);
+        true
+    }
+
+    /// Builds an order-independent signature of a batch of tool calls.
+    fn signature(tool_calls: &[ToolCall]) -> Vec<(String, Value)> {
+        let mut calls: Vec<(String, Value)> = tool_calls
+            .iter()
+            .map(|call| (call.tool_name.clone(), call.tool_args.clone()))
+            .collect();
+        calls.sort_by_cached_key(|(name, args)| (name.clone(), args.to_string()));
+        calls
+    }
+}
+
+impl Default for ToolLoopGuard {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_TOOL_REPEATS)
+    }
+}
+
+/// Reads a positive limit from `var`.
+fn env_limit(var: &str) -> Option<usize> {
+    let value = std::env::var(var).ok()?;
+    match value.parse::<usize>() {
+        Ok(limit) if limit > 0 => Some(limit),
+        _ => {
+            warn!("Ignoring invalid {} '{}'", var, value);
+            None
+        }
+    }
+}
+
 /// Parses all tool calls from a response string using the new special format.
 /// For each function call, expects this format:
 /// <｜tool▁calls▁begin｜><｜tool▁call▁begin｜>function<｜tool▁sep｜>function_name
 /// ```json
 /// {...}
 /// ```<｜tool▁call▁end｜><｜tool▁calls▁end｜>
+///
+/// Only the calls the response ends with are honored: a call followed by anything but
+/// whitespace, wrapper tokens or further calls is taken to be the model quoting the
+/// syntax, say while explaining it, and is ignored.
 pub fn parse_tool_calls(response: &str) -> Vec<ToolCall> {
-    let mut calls = Vec::new();
-    // Regex for the new special format
-    let re_new = Regex::new(
-        r"<\u{FF5C}tool▁call▁begin\u{FF5C}>(?P<type>\w+)<\u{FF5C}tool▁sep\u{FF5C}>(?P<name>\w+)\s*```json\s*(?P<args>\{.*?\})\s*```<\u{FF5C}tool▁call▁end\u{FF5C}>"
-    ).unwrap();
-    // Regex for the format without tags: function function_name ```json {...} ```
-    let re_old =
-        Regex::new(r"function\s+(?P<name>\w+)\s*```json\s*(?P<args>\{.*?\})\s*```").unwrap();
+    // (start, end, call) for every call in either format
+    let mut found = Vec::new();
 
     // First, try to find all new-format tool calls
-    for cap in re_new.captures_iter(response) {
+    for cap in TOOL_CALL_RE.captures_iter(response) {
         let tool_type = cap
             .name("type")
             .map(|m| m.as_str().to_string())
@@ -50,15 +427,20 @@ pub fn parse_tool_calls(response: &str) -> Vec<ToolCall> {
             .unwrap_or_default();
         let args_str = cap.name("args").map(|m| m.as_str()).unwrap_or("{}");
         if let Ok(tool_args) = serde_json::from_str(args_str) {
-            calls.push(ToolCall {
-                tool_type: tool_type.clone(),
-                tool_name,
-                tool_args,
-            });
+            let span = cap.get(0).unwrap();
+            found.push((
+                span.start(),
+                span.end(),
+                ToolCall {
+                    tool_type: tool_type.clone(),
+                    tool_name,
+                    tool_args,
+                },
+            ));
         }
     }
     // Then, try to find all old-format tool calls (if any)
-    for cap in re_old.captures_iter(response) {
+    for cap in TOOL_CALL_UNTAGGED_RE.captures_iter(response) {
         let tool_type = "function".to_string();
         let tool_name = cap
             .name("name")
@@ -66,34 +448,174 @@ pub fn parse_tool_calls(response: &str) -> Vec<ToolCall> {
             .unwrap_or_default();
         let args_str = cap.name("args").map(|m| m.as_str()).unwrap_or("{}");
         if let Ok(tool_args) = serde_json::from_str(args_str) {
-            calls.push(ToolCall {
-                tool_type: tool_type.clone(),
-                tool_name,
-                tool_args,
-            });
+            let span = cap.get(0).unwrap();
+            found.push((
+                span.start(),
+                span.end(),
+                ToolCall {
+                    tool_type: tool_type.clone(),
+                    tool_name,
+                    tool_args,
+                },
+            ));
+        }
+    }
+    found.sort_by_key(|(start, _, _)| *start);
+
+    // Walk back from the end, keeping calls until one is followed by prose
+    let mut tail_start = response.len();
+    let mut first_kept = found.len();
+    for (i, (start, end, _)) in found.iter().enumerate().rev() {
+        if *end > tail_start || !is_call_separator(&response[*end..tail_start]) {
+            break;
         }
+        tail_start = *start;
+        first_kept = i;
     }
-    calls
+    if first_kept > 0 {
+        debug!(
+            "Ignoring {} tool call(s) followed by text, treating them as quoted",
+            first_kept
+        );
+    }
+    found
+        .into_iter()
+        .skip(first_kept)
+        .map(|(_, _, call)| call)
+        .collect()
+}
+
+/// Whether `text` between or after tool calls holds nothing but whitespace and
+/// wrapper tokens.
+fn is_call_separator(text: &str) -> bool {
+    TOOL_CALLS_WRAPPER_RE
+        .replace_all(text, "")
+        .trim()
+        .is_empty()
+}
+
+/// Whether `response` holds a finished batch of tool calls, closed by the end-of-calls
+/// token, so a streamed response can stop there rather than wait for the rest.
+pub fn has_complete_tool_calls(response: &str) -> bool {
+    response.contains(TOOL_CALLS_END) && !parse_tool_calls(response).is_empty()
+}
+
+/// Removes any tool-call markup from a response, leaving only the plain-text answer.
+pub fn strip_tool_calls(response: &str) -> String {
+    let stripped = TOOL_CALL_RE.replace_all(response, "");
+    let stripped = TOOL_CALL_UNTAGGED_RE.replace_all(&stripped, "");
+    TOOL_CALLS_WRAPPER_RE
+        .replace_all(&stripped, "")
+        .trim()
+        .to_string()
 }
 
 /// Executes a list of tool calls using the provided tools.
-/// Returns a Vec of (tool_name, result or error string).
+/// Returns a Vec of (tool_name, result), one per call and in the same order.
 pub async fn execute_tool_calls(
     tool_calls: &[ToolCall],
     tools: &[Box<dyn AiTool>],
-) -> Vec<(String, Result<Value, String>)> {
+    ctx: &ToolContext<'_>,
+) -> Vec<(String, Result<Value, ToolError>)> {
     let mut results = Vec::new();
     info!("Executing {} tool calls", tool_calls.len());
     for call in tool_calls {
         if let Some(tool) = tools.iter().find(|t| t.name() == call.tool_name) {
             info!("Executing tool: {}", call.tool_name);
-            match tool.execute(&call.tool_args).await {
-                Ok(res) => results.push((call.tool_name.clone(), Ok(res))),
-                Err(e) => results.push((call.tool_name.clone(), Err(format!("Error: {}", e)))),
-            }
+            let result = match tokio::time::timeout(
+                TOOL_TIMEOUT,
+                tool.execute(&call.tool_args, ctx)
+                    .instrument(tracing::info_span!("tool", name = %call.tool_name)),
+            )
+            .await
+            {
+                Ok(Ok(res)) => Ok(res),
+                Ok(Err(e)) => Err(ToolError::from(e)),
+                Err(_) => Err(ToolError::Timeout),
+            };
+            results.push((call.tool_name.clone(), result));
         } else {
-            results.push((call.tool_name.clone(), Err("Tool not found".to_string())));
+            // List the real tools so the model can correct itself
+            let available = tools.iter().map(|t| t.name().to_string()).collect();
+            results.push((
+                call.tool_name.clone(),
+                Err(ToolError::NotFound {
+                    name: call.tool_name.clone(),
+                    available,
+                }),
+            ));
         }
     }
     results
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn internal_addresses_are_not_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "169.254.169.254",
+            "100.64.0.1",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{} is internal", ip);
+        }
+        assert!(is_public_ip("1.1.1.1".parse().unwrap()));
+        assert!(is_public_ip("2606:4700::1111".parse().unwrap()));
+    }
+
+    fn call(name: &str, args: Value) -> ToolCall {
+        ToolCall {
+            tool_type: "function".to_string(),
+            tool_name: name.to_string(),
+            tool_args: args,
+        }
+    }
+
+    #[test]
+    fn loop_guard_trips_on_reordered_batch() {
+        let a = call("search", serde_json::json!({ "query": "rust" }));
+        let b = call(
+            "website",
+            serde_json::json!({ "website": "https://example.com" }),
+        );
+        let mut guard = ToolLoopGuard::new(1);
+        assert!(guard.check(&[a.clone(), b.clone()]));
+        assert!(!guard.check(&[b, a]));
+        assert!(!guard.hit_iteration_cap());
+    }
+
+    #[test]
+    fn loop_guard_allows_different_batches() {
+        let a = call("search", serde_json::json!({ "query": "rust" }));
+        let b = call(
+            "website",
+            serde_json::json!({ "website": "https://example.com" }),
+        );
+        let c = call("search", serde_json::json!({ "query": "tokio" }));
+        let mut guard = ToolLoopGuard::new(1);
+        assert!(guard.check(&[a.clone(), b.clone()]));
+        assert!(guard.check(&[b.clone(), c]));
+        assert!(guard.check(&[a, b]));
+    }
+
+    #[tokio::test]
+    async fn internal_urls_are_refused() {
+        for url in [
+            "http://127.0.0.1/",
+            "http://localhost:8080/admin",
+            "http://[::1]/",
+            "http://169.254.169.254/latest/meta-data/",
+            "file:///etc/passwd",
+        ] {
+            assert!(get_public(url).await.is_err(), "{} was fetched", url);
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use anyhow::{anyhow, Error};
+use reqwest::multipart::{Form, Part};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::debug;
+
+use crate::tools::{get_public, http_client, AiTool, ToolContext, ToolError};
+
+const DEFAULT_OCR_URL: &str = "https://api.ocr.space/parse/image";
+const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024; // OCR endpoints reject anything much bigger
+
+/// Image types the OCR endpoint accepts, with the file extension it expects for each.
+const SUPPORTED_FORMATS: &[(&str, &str)] = &[
+    ("image/png", "png"),
+    ("image/jpeg", "jpg"),
+    ("image/gif", "gif"),
+    ("image/bmp", "bmp"),
+    ("image/tiff", "tif"),
+    ("image/webp", "webp"),
+];
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct OcrResponse {
+    #[serde(default)]
+    parsed_results: Vec<ParsedResult>,
+    #[serde(default)]
+    is_errored_on_processing: bool,
+    /// Either a string or a list of strings, depending on the failure.
+    #[serde(default)]
+    error_message: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ParsedResult {
+    #[serde(default)]
+    parsed_text: String,
+}
+
+/// Tool that reads the text in an image through an OCR.space-compatible endpoint.
+pub struct OcrTool {
+    url: String,
+    api_key: Option<String>,
+}
+
+impl OcrTool {
+    pub fn new(url: &str, api_key: Option<String>) -> Self {
+        Self {
+            url: url.to_string(),
+            api_key,
+        }
+    }
+
+    /// Reads `OCR_URL` (defaults to OCR.space) and `OCR_API_KEY`.
+    pub fn from_env() -> Self {
+        let url = std::env::var("OCR_URL").unwrap_or_else(|_| DEFAULT_OCR_URL.to_string());
+        let api_key = std::env::var("OCR_API_KEY").ok().filter(|k| !k.is_empty());
+        Self::new(&url, api_key)
+    }
+}
+
+#[async_trait::async_trait]
+impl AiTool for OcrTool {
+    fn name(&self) -> &str {
+        "ocr"
+    }
+
+    fn description(&self) -> &str {
+        r#"Reads the text in an image, such as a screenshot of a post or a photo of a sign.
+Parameters:
+- `image_url`: The URL of the image (PNG, JPEG, GIF, BMP, TIFF or WebP).
+Returns the recognized text.
+Example usage: { "image_url": "https://example.com/screenshot.png" }
+"#
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "image_url": { "type": "string", "description": "URL of the image to read" }
+            },
+            "required": ["image_url"]
+        })
+    }
+
+    async fn execute(&self, args: &Value, _ctx: &ToolContext<'_>) -> Result<Value, Error> {
+        let image_url = args
+            .get("image_url")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::InvalidArgs("Missing 'image_url' parameter".to_string()))?;
+        let api_key = self
+            .api_key
+            .as_deref()
+            .ok_or_else(|| anyhow!("OCR is not configured (OCR_API_KEY is not set)"))?;
+
+        let resp = get_public(image_url)
+            .await
+            .map_err(|e| anyhow!("Could not download image: {}", e))?;
+        if !resp.status().is_success() {
+            return Err(anyhow!("Could not download image: HTTP {}", resp.status()));
+        }
+
+        let content_type = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(';').next())
+            .map(|v| v.trim().to_ascii_lowercase())
+            .unwrap_or_default();
+        let (mime, extension) = SUPPORTED_FORMATS
+            .iter()
+            .find(|(mime, _)| *mime == content_type)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unsupported image format '{}', expected PNG, JPEG, GIF, BMP, TIFF or WebP",
+                    content_type
+                )
+            })?;
+        if resp
+            .content_length()
+            .is_some_and(|len| len as usize > MAX_IMAGE_BYTES)
+        {
+            return Err(anyhow!(
+                "Image is too large, the limit is {} bytes",
+                MAX_IMAGE_BYTES
+            ));
+        }
+
+        let image = resp
+            .bytes()
+            .await
+            .map_err(|e| anyhow!("Could not download image: {}", e))?;
+        if image.len() > MAX_IMAGE_BYTES {
+            return Err(anyhow!(
+                "Image is too large, the limit is {} bytes",
+                MAX_IMAGE_BYTES
+            ));
+        }
+        debug!("Downloaded {} byte {} image", image.len(), mime);
+
+        let part = Part::bytes(image.to_vec())
+            .file_name(format!("image.{}", extension))
+            .mime_str(mime)?;
+        let form = Form::new().part("file", part);
+
+        let resp = http_client()
+            .post(&self.url)
+            .header("apikey", api_key)
+            .multipart(form)
+            .send()
+            .await
+            .map_err(|e| anyhow!("Request error: {}", e))?;
+
+        debug!("Response status: {}", resp.status());
+
+        let ocr: OcrResponse = resp
+            .json()
+            .await
+            .map_err(|e| anyhow!("Body error: {}", e))?;
+        if ocr.is_errored_on_processing {
+            let message = match &ocr.error_message {
+                Value::String(s) => s.clone(),
+                Value::Array(messages) => messages
+                    .iter()
+                    .filter_map(|m| m.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                _ => "unknown error".to_string(),
+            };
+            return Err(anyhow!("OCR failed: {}", message));
+        }
+
+        let text = ocr
+            .parsed_results
+            .iter()
+            .map(|r| r.parsed_text.trim())
+            .filter(|t| !t.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Ok(json!({
+            "found": !text.is_empty(),
+            "text": text,
+        }))
+    }
+}
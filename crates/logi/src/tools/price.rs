@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Error};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::debug;
+
+use crate::tools::{http_client, AiTool, ToolContext, ToolError};
+
+const COINGECKO_API: &str = "https://api.coingecko.com/api/v3";
+const DEFAULT_STOCK_API: &str = "https://finnhub.io/api/v1"; // Finnhub-compatible `/quote` endpoint
+const PRICE_CACHE_TTL: Duration = Duration::from_secs(60); // Free tiers allow a few calls a minute
+
+#[derive(Deserialize)]
+struct CoinSearch {
+    coins: Vec<Coin>,
+}
+
+#[derive(Deserialize)]
+struct Coin {
+    id: String,
+    name: String,
+    symbol: String,
+}
+
+/// Finnhub's quote: current price, change in percent and quote time.
+#[derive(Deserialize)]
+struct StockQuote {
+    c: f64,
+    dp: Option<f64>,
+    t: i64,
+}
+
+/// Tool that looks up the current price of a cryptocurrency or stock.
+///
+/// Crypto prices come from CoinGecko. Stocks need `STOCK_API_KEY` for a
+/// Finnhub-compatible API, whose base URL can be changed with `STOCK_API_URL`.
+pub struct PriceTool {
+    stock_api: String,
+    stock_api_key: Option<String>,
+    cache: Mutex<HashMap<String, (Instant, Value)>>,
+}
+
+impl PriceTool {
+    pub fn new(stock_api: String, stock_api_key: Option<String>) -> Self {
+        Self {
+            stock_api,
+            stock_api_key,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reads `STOCK_API_URL` and `STOCK_API_KEY`.
+    pub fn from_env() -> Self {
+        let stock_api = std::env::var("STOCK_API_URL")
+            .ok()
+            .map(|url| url.trim().trim_end_matches('/').to_string())
+            .filter(|url| !url.is_empty())
+            .unwrap_or_else(|| DEFAULT_STOCK_API.to_string());
+        Self::new(
+            stock_api,
+            std::env::var("STOCK_API_KEY")
+                .ok()
+                .filter(|k| !k.is_empty()),
+        )
+    }
+
+    fn cached(&self, key: &str) -> Option<Value> {
+        let cache = self.cache.lock().unwrap();
+        cache
+            .get(key)
+            .filter(|(at, _)| at.elapsed() < PRICE_CACHE_TTL)
+            .map(|(_, value)| value.clone())
+    }
+
+    fn store(&self, key: String, value: Value) {
+        let mut cache = self.cache.lock().unwrap();
+        cache.retain(|_, (at, _)| at.elapsed() < PRICE_CACHE_TTL);
+        cache.insert(key, (Instant::now(), value));
+    }
+
+    /// Finds the CoinGecko coin for a ticker. Search results are ranked by market cap,
+    /// so the first exact symbol match is the one people usually mean.
+    async fn find_coin(&self, symbol: &str) -> Result<Option<Coin>, Error> {
+        let search: CoinSearch = http_client()
+            .get(format!("{}/search", COINGECKO_API))
+            .query(&[("query", symbol)])
+            .send()
+            .await
+            .map_err(|e| anyhow!("Request error: {}", e))?
+            .error_for_status()
+            .map_err(|e| anyhow!("CoinGecko API error: {}", e))?
+            .json()
+            .await
+            .map_err(|e| anyhow!("Body error: {}", e))?;
+        Ok(search
+            .coins
+            .into_iter()
+            .find(|coin| coin.symbol.eq_ignore_ascii_case(symbol)))
+    }
+
+    async fn crypto_price(&self, coin: Coin, vs: &str) -> Result<Value, Error> {
+        let vs = vs.to_lowercase();
+        let prices: HashMap<String, HashMap<String, f64>> = http_client()
+            .get(format!("{}/simple/price", COINGECKO_API))
+            .query(&[
+                ("ids", coin.id.as_str()),
+                ("vs_currencies", vs.as_str()),
+                ("include_24hr_change", "true"),
+                ("include_last_updated_at", "true"),
+            ])
+            .send()
+            .await
+            .map_err(|e| anyhow!("Request error: {}", e))?
+            .error_for_status()
+            .map_err(|e| anyhow!("CoinGecko API error: {}", e))?
+            .json()
+            .await
+            .map_err(|e| anyhow!("Body error: {}", e))?;
+
+        let Some(price) = prices.get(&coin.id).and_then(|p| p.get(&vs)) else {
+            return Ok(json!({
+                "symbol": coin.symbol.to_uppercase(),
+                "found": false,
+                "message": format!("No {} price for {}", vs.to_uppercase(), coin.name),
+            }));
+        };
+        let quote = &prices[&coin.id];
+        Ok(json!({
+            "symbol": coin.symbol.to_uppercase(),
+            "name": coin.name,
+            "market": "crypto",
+            "found": true,
+            "price": price,
+            "currency": vs.to_uppercase(),
+            "change_24h_percent": quote.get(&format!("{}_24h_change", vs)),
+            "quoted_at": quote
+                .get("last_updated_at")
+                .and_then(|&at| format_unix(at as i64)),
+        }))
+    }
+
+    async fn stock_price(&self, symbol: &str, vs: &str) -> Result<Value, Error> {
+        let Some(key) = &self.stock_api_key else {
+            return Err(ToolError::InvalidArgs(
+                "Stock prices aren't available, only crypto".to_string(),
+            )
+            .into());
+        };
+        if !vs.eq_ignore_ascii_case("USD") {
+            return Err(
+                ToolError::InvalidArgs("Stock prices are only quoted in USD".to_string()).into(),
+            );
+        }
+
+        let quote: StockQuote = http_client()
+            .get(format!("{}/quote", self.stock_api))
+            .query(&[("symbol", symbol), ("token", key.as_str())])
+            .send()
+            .await
+            .map_err(|e| anyhow!("Request error: {}", e))?
+            .error_for_status()
+            .map_err(|e| anyhow!("Stock API error: {}", e))?
+            .json()
+            .await
+            .map_err(|e| anyhow!("Body error: {}", e))?;
+
+        // Unknown symbols come back as an all-zero quote rather than an error
+        if quote.t == 0 {
+            return Ok(json!({
+                "symbol": symbol,
+                "found": false,
+                "message": format!("No stock or cryptocurrency with the symbol {}", symbol),
+            }));
+        }
+        Ok(json!({
+            "symbol": symbol,
+            "market": "stock",
+            "found": true,
+            "price": quote.c,
+            "currency": "USD",
+            "change_24h_percent": quote.dp,
+            "quoted_at": format_unix(quote.t),
+        }))
+    }
+}
+
+fn format_unix(at: i64) -> Option<String> {
+    time::OffsetDateTime::from_unix_timestamp(at)
+        .ok()?
+        .format(&time::format_description::well_known::Rfc3339)
+        .ok()
+}
+
+#[async_trait::async_trait]
+impl AiTool for PriceTool {
+    fn name(&self) -> &str {
+        "price"
+    }
+
+    fn description(&self) -> &str {
+        r#"Looks up the current price of a cryptocurrency or stock.
+Parameters:
+- `symbol`: The ticker, e.g. "BTC" or "AAPL".
+- `vs`: (Optional) The currency to quote in. Defaults to "USD".
+- `market`: (Optional) "crypto" or "stock". By default crypto is tried first.
+Returns the price, the change over the last 24 hours in percent and when it was quoted.
+Example usage: { "symbol": "BTC", "vs": "USD" }
+"#
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "symbol": { "type": "string", "description": "The ticker, e.g. BTC or AAPL" },
+                "vs": { "type": "string", "description": "Currency to quote in", "default": "USD" },
+                "market": { "type": "string", "enum": ["crypto", "stock"] }
+            },
+            "required": ["symbol"]
+        })
+    }
+
+    async fn execute(&self, args: &Value, _ctx: &ToolContext<'_>) -> Result<Value, Error> {
+        let symbol = args
+            .get("symbol")
+            .and_then(|v| v.as_str())
+            .map(|s| s.trim().trim_start_matches('$').to_uppercase())
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| ToolError::InvalidArgs("Missing 'symbol' parameter".to_string()))?;
+        let vs = args
+            .get("vs")
+            .and_then(|v| v.as_str())
+            .map(|s| s.trim().to_uppercase())
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "USD".to_string());
+        let market = args.get("market").and_then(|v| v.as_str());
+        if !matches!(market, None | Some("crypto") | Some("stock")) {
+            return Err(ToolError::InvalidArgs(
+                "'market' must be \"crypto\" or \"stock\"".to_string(),
+            )
+            .into());
+        }
+
+        let key = format!("{}:{}:{}", market.unwrap_or("any"), symbol, vs);
+        if let Some(cached) = self.cached(&key) {
+            debug!("Price cache hit for {}", key);
+            return Ok(cached);
+        }
+
+        let coin = if market == Some("stock") {
+            None
+        } else {
+            self.find_coin(&symbol).await?
+        };
+        let result = match coin {
+            Some(coin) => self.crypto_price(coin, &vs).await?,
+            None if market == Some("crypto") || self.stock_api_key.is_none() => json!({
+                "symbol": symbol,
+                "found": false,
+                "message": format!("No cryptocurrency with the symbol {}", symbol),
+            }),
+            None => self.stock_price(&symbol, &vs).await?,
+        };
+
+        self.store(key, result.clone());
+        Ok(result)
+    }
+}
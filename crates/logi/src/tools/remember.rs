@@ -0,0 +1,166 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Error};
+use serde_json::{json, Value};
+use tracing::info;
+
+use crate::embed::Embedder;
+use crate::tools::{AiTool, ToolContext, ToolError};
+use crate::vdb::{MemoryEntry, MemoryStore};
+
+const MAX_FACT_CHARS: usize = 500; // Facts are meant to be short, not whole posts
+const MAX_FACTS_PER_USER: u64 = 50; // Keeps one user from filling the store
+const MAX_GLOBAL_FACTS: u64 = 200; // Global facts are shared by everyone, so allow a few more
+
+tokio::task_local! {
+    /// DID of the user whose post is being replied to, so user-scoped facts know who
+    /// they belong to.
+    pub static POSTER_DID: String;
+}
+
+/// Tag carried by the facts of one user.
+pub fn user_fact_tag(did: &str) -> String {
+    format!("user:{}", did)
+}
+
+/// Tool that stores a fact the user asked to be remembered as long-term memory.
+pub struct RememberTool {
+    vdb: Arc<MemoryStore>,
+    emb: Arc<Embedder>,
+}
+
+impl RememberTool {
+    pub fn new(vdb: Arc<MemoryStore>, emb: Arc<Embedder>) -> Self {
+        Self { vdb, emb }
+    }
+}
+
+#[async_trait::async_trait]
+impl AiTool for RememberTool {
+    fn name(&self) -> &str {
+        "remember"
+    }
+
+    fn description(&self) -> &str {
+        r#"Stores a fact in long-term memory. Only use this when the user explicitly asks you to remember something.
+Parameters:
+- `fact`: The fact to remember, as a short self-contained sentence.
+- `scope`: "user" for facts about the person asking (default), or "global" for facts everyone should get.
+Example usage: { "fact": "their cat is called Miso", "scope": "user" }
+"#
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "fact": { "type": "string", "description": "A short self-contained sentence" },
+                "scope": { "type": "string", "enum": ["user", "global"], "default": "user" }
+            },
+            "required": ["fact"]
+        })
+    }
+
+    async fn execute(&self, args: &Value, ctx: &ToolContext<'_>) -> Result<Value, Error> {
+        let fact = args
+            .get("fact")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|f| !f.is_empty())
+            .ok_or_else(|| ToolError::InvalidArgs("Missing 'fact' parameter".to_string()))?;
+        if fact.chars().count() > MAX_FACT_CHARS {
+            return Err(ToolError::InvalidArgs(format!(
+                "'fact' must be at most {} characters",
+                MAX_FACT_CHARS
+            ))
+            .into());
+        }
+
+        let did = POSTER_DID
+            .try_with(|did| did.clone())
+            .map_err(|_| anyhow!("Facts can only be remembered when replying to a post"))?;
+        let (scope_tag, limit) = match args.get("scope").and_then(|v| v.as_str()) {
+            None | Some("user") => (user_fact_tag(&did), MAX_FACTS_PER_USER),
+            Some("global") => ("global".to_string(), MAX_GLOBAL_FACTS),
+            Some(other) => {
+                return Err(ToolError::InvalidArgs(format!(
+                    "Invalid 'scope' '{}', must be 'user' or 'global'",
+                    other
+                ))
+                .into())
+            }
+        };
+
+        if ctx.dry_run {
+            info!(fact, scope = %scope_tag, "[dry run] would remember");
+            return Ok(json!({ "remembered": false, "dry_run": true, "scope": scope_tag }));
+        }
+
+        let tags = vec!["ltm".to_string(), "fact".to_string(), scope_tag.clone()];
+        let stored = self.vdb.count_tagged(tags.clone()).await?;
+        if stored >= limit {
+            return Ok(json!({
+                "remembered": false,
+                "message": format!("Already remembering {} facts for this scope, which is the limit", stored),
+            }));
+        }
+
+        let embedding = self
+            .emb
+            .embed(vec![fact.to_string()])?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("Embedder returned no vector"))?;
+        // the same fact in the same scope overwrites itself instead of piling up
+        let id = uuid::Uuid::new_v5(
+            &uuid::Uuid::NAMESPACE_DNS,
+            format!("fact:{}:{}", scope_tag, fact).as_bytes(),
+        )
+        .to_string();
+
+        self.vdb
+            .put(MemoryEntry {
+                id,
+                content: fact.to_string(),
+                embedding,
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs() as i64,
+                tags,
+                role: "user".to_string(),
+                entry_type: "fact".to_string(),
+                conversation_id: did,
+            })
+            .await?;
+
+        Ok(json!({ "remembered": true, "scope": scope_tag }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn dry_run_leaves_memory_alone() {
+        // unconnected, so any read or write would fail the call
+        let vdb = Arc::new(MemoryStore::unconnected("aigis-test").unwrap());
+        let tool = RememberTool::new(vdb, Arc::new(Embedder::stub(vec![1.0, 0.0])));
+        let ctx = ToolContext {
+            dry_run: true,
+            ..ToolContext::default()
+        };
+
+        let result = POSTER_DID
+            .scope(
+                "did:plc:aigistestuser".to_string(),
+                tool.execute(&json!({ "fact": "likes tea" }), &ctx),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result["remembered"], false);
+        assert_eq!(result["dry_run"], true);
+    }
+}
@@ -0,0 +1,187 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Error};
+use serde_json::{json, Value};
+use tracing::info;
+
+use crate::reminders::{unix_now, Reminder, ReminderStore, REPLY_TARGET};
+use crate::tools::{AiTool, ToolContext, ToolError};
+
+const MAX_REMINDER_DELAY: Duration = Duration::from_secs(365 * 24 * 60 * 60); // Don't accept reminders further out than a year
+
+/// Tool that schedules a reply to the current post at a later time.
+pub struct ReminderTool {
+    store: Arc<ReminderStore>,
+}
+
+impl ReminderTool {
+    pub fn new(store: Arc<ReminderStore>) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait::async_trait]
+impl AiTool for ReminderTool {
+    fn name(&self) -> &str {
+        "reminder"
+    }
+
+    fn description(&self) -> &str {
+        r#"Schedules a reminder that will be posted as a reply to the current post later.
+Parameters:
+- `message`: What to remind the user about.
+- `in`: How long from now, e.g. "30m", "2h", "1d", "1h30m". Units: s, m, h, d, w.
+- `at`: Alternatively, an RFC 3339 time such as "2025-07-01T09:00:00Z".
+Example usage: { "message": "check the oven", "in": "2h" }
+"#
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "message": { "type": "string", "description": "What to remind the user about" },
+                "in": { "type": "string", "description": "Delay such as \"30m\" or \"1h30m\"" },
+                "at": { "type": "string", "description": "RFC 3339 time, instead of `in`" }
+            },
+            "required": ["message"]
+        })
+    }
+
+    async fn execute(&self, args: &Value, ctx: &ToolContext<'_>) -> Result<Value, Error> {
+        let message = args
+            .get("message")
+            .and_then(|v| v.as_str())
+            .filter(|m| !m.trim().is_empty())
+            .ok_or_else(|| ToolError::InvalidArgs("Missing 'message' parameter".to_string()))?;
+        let now = unix_now();
+        let due_at = match (
+            args.get("in").and_then(|v| v.as_str()),
+            args.get("at").and_then(|v| v.as_str()),
+        ) {
+            (Some(delay), _) => {
+                let delay = parse_duration(delay).ok_or_else(|| {
+                    ToolError::InvalidArgs(format!("Invalid 'in' duration: {}", delay))
+                })?;
+                // Checked up front so a huge delay can't overflow the timestamp
+                if delay > MAX_REMINDER_DELAY {
+                    return Err(too_far_ahead().into());
+                }
+                now + delay.as_secs() as i64
+            }
+            (None, Some(at)) => {
+                time::OffsetDateTime::parse(at, &time::format_description::well_known::Rfc3339)
+                    .map_err(|e| ToolError::InvalidArgs(format!("Invalid 'at' time: {}", e)))?
+                    .unix_timestamp()
+            }
+            (None, None) => {
+                return Err(
+                    ToolError::InvalidArgs("Missing 'in' or 'at' parameter".to_string()).into(),
+                )
+            }
+        };
+        if due_at <= now {
+            return Err(
+                ToolError::InvalidArgs("Reminder time must be in the future".to_string()).into(),
+            );
+        }
+        if due_at - now > MAX_REMINDER_DELAY.as_secs() as i64 {
+            return Err(too_far_ahead().into());
+        }
+
+        let reply = REPLY_TARGET
+            .try_with(|reply| reply.clone())
+            .map_err(|_| anyhow!("Reminders can only be set when replying to a post"))?;
+        let id = uuid::Uuid::new_v5(
+            &uuid::Uuid::NAMESPACE_URL,
+            format!("{}#{}#{}", reply.parent.uri, due_at, message).as_bytes(),
+        )
+        .to_string();
+
+        if ctx.dry_run {
+            info!(id, due_at, message, "[dry run] would schedule reminder");
+            return Ok(json!({
+                "scheduled": false,
+                "dry_run": true,
+                "due_in_seconds": due_at - now,
+            }));
+        }
+
+        self.store
+            .add(Reminder {
+                id: id.clone(),
+                due_at,
+                message: message.to_string(),
+                reply,
+                attempts: 0,
+            })
+            .await?;
+
+        Ok(json!({
+            "scheduled": true,
+            "id": id,
+            "due_in_seconds": due_at - now,
+        }))
+    }
+}
+
+fn too_far_ahead() -> ToolError {
+    ToolError::InvalidArgs("Reminders can be set at most a year ahead".to_string())
+}
+
+/// Parses durations like "90s", "2h" or "1h30m".
+fn parse_duration(input: &str) -> Option<Duration> {
+    let mut total = 0u64;
+    let mut number = String::new();
+    for c in input.trim().chars().filter(|c| !c.is_whitespace()) {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let value: u64 = number.parse().ok()?;
+        number.clear();
+        let unit = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return None,
+        };
+        total = total.checked_add(value.checked_mul(unit)?)?;
+    }
+    // A trailing bare number has no unit, which is ambiguous
+    if !number.is_empty() || total == 0 {
+        return None;
+    }
+    Some(Duration::from_secs(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn huge_delays_are_refused_without_overflowing() {
+        let store = ReminderStore::load(std::env::temp_dir().join("reminder-test-unused.json"))
+            .await
+            .unwrap();
+        let tool = ReminderTool::new(Arc::new(store));
+        for delay in ["9223372036854775807s", "20000000000000w", "53w"] {
+            let err = tool
+                .execute(
+                    &json!({ "message": "hi", "in": delay }),
+                    &ToolContext::default(),
+                )
+                .await
+                .unwrap_err();
+            assert!(
+                err.to_string().contains("at most a year"),
+                "{}: {}",
+                delay,
+                err
+            );
+        }
+    }
+}
@@ -1,15 +1,25 @@
-use crate::tools::AiTool;
+use crate::tools::{http_client, AiTool, ToolContext, ToolError};
 use anyhow::anyhow;
-use reqwest;
+use reqwest::Url;
 use scraper::{Html, Selector};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Value};
+
+const DDG_HTML_URL: &str = "https://html.duckduckgo.com/html/";
+const MAX_PAGE: usize = 5; // Each page is a separate request, so don't let the model wander too far
 
 /// Parameters for the DuckDuckGo search tool.
 #[derive(Deserialize)]
 struct SearchParams {
     /// The search query to send to DuckDuckGo.
     query: String,
+    /// 1-based page of results to return.
+    #[serde(default = "default_page")]
+    page: usize,
+}
+
+fn default_page() -> usize {
+    1
 }
 
 /// Represents a single search result.
@@ -17,9 +27,16 @@ struct SearchParams {
 struct SearchResult {
     title: String,
     link: String,
+    domain: String,
     snippet: String,
 }
 
+/// A parsed page of results, plus the form fields DuckDuckGo wants posted back for the next page.
+struct SearchPage {
+    results: Vec<SearchResult>,
+    next_params: Option<Vec<(String, String)>>,
+}
+
 /// Tool for searching DuckDuckGo.
 pub struct DDGSearchTool;
 
@@ -43,63 +60,164 @@ cats -site:example.com	exclude example.com from results
 intitle:dogs	title contains "dogs"
 inurl:cats	URL contains "cats"
 
-Usage: { \"query\": \"rust async traits\" }"#
+Parameters:
+- `query`: The search query.
+- `page`: Which page of results to return, starting at 1 (default is 1). Use `has_more` in the result to tell whether another page exists.
+
+Usage: { \"query\": \"rust async traits\", \"page\": 1 }"#
     }
 
-    async fn execute(&self, args: &Value) -> anyhow::Result<Value> {
-        let params: SearchParams = serde_json::from_value(args.clone())
-            .map_err(|_| anyhow!("Missing or invalid 'query' parameter"))?;
-        let client = reqwest::Client::new();
-        let url = format!("https://html.duckduckgo.com/html/?q={}", params.query);
-        let resp = client
-            .get(&url)
-            .header("Accept", "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8")
-            .header("User-Agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 10.15; rv:140.0) Gecko/20100101 Firefox/140.0")
-            .send()
-            .await
-            .map_err(|e| anyhow!("Request error: {}", e))?;
-        let body = resp
-            .text()
-            .await
-            .map_err(|e| anyhow!("Body error: {}", e))?;
-        let document = Html::parse_document(&body);
-
-        let result_selector = Selector::parse(".web-result").unwrap();
-        let result_title_selector = Selector::parse(".result__a").unwrap();
-        let result_url_selector = Selector::parse(".result__url").unwrap();
-        let result_snippet_selector = Selector::parse(".result__snippet").unwrap();
-
-        let results = document
-            .select(&result_selector)
-            .filter_map(|result| {
-                let title = result
-                    .select(&result_title_selector)
-                    .next()
-                    .map(|n| n.text().collect::<Vec<_>>().join(""))
-                    .unwrap_or_default();
-                let link = result
-                    .select(&result_url_selector)
-                    .next()
-                    .map(|n| n.text().collect::<Vec<_>>().join("").trim().to_string())
-                    .unwrap_or_default();
-                let snippet = result
-                    .select(&result_snippet_selector)
-                    .next()
-                    .map(|n| n.text().collect::<Vec<_>>().join(""))
-                    .unwrap_or_default();
-
-                if !title.is_empty() && !link.is_empty() {
-                    Some(SearchResult {
-                        title,
-                        link,
-                        snippet,
-                    })
-                } else {
-                    None
-                }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": { "type": "string", "description": "The search query" },
+                "page": { "type": "integer", "minimum": 1, "default": 1 }
+            },
+            "required": ["query"]
+        })
+    }
+
+    async fn execute(&self, args: &Value, _ctx: &ToolContext<'_>) -> anyhow::Result<Value> {
+        let params: SearchParams = serde_json::from_value(args.clone()).map_err(|_| {
+            ToolError::InvalidArgs("Missing or invalid 'query' parameter".to_string())
+        })?;
+        if params.page == 0 || params.page > MAX_PAGE {
+            return Err(ToolError::InvalidArgs(format!(
+                "'page' must be between 1 and {}",
+                MAX_PAGE
+            ))
+            .into());
+        }
+
+        let mut page = fetch_page(
+            http_client()
+                .get(DDG_HTML_URL)
+                .query(&[("q", &params.query)]),
+        )
+        .await?;
+        // DuckDuckGo's HTML endpoint has no page parameter; each page is reached by
+        // posting back the hidden form fields of the previous page's "Next" button.
+        for _ in 1..params.page {
+            let Some(next_params) = page.next_params.take() else {
+                return Ok(serde_json::json!({
+                    "page": params.page,
+                    "has_more": false,
+                    "results": [],
+                }));
+            };
+            page = fetch_page(http_client().post(DDG_HTML_URL).form(&next_params)).await?;
+        }
+
+        Ok(serde_json::json!({
+            "page": params.page,
+            "has_more": page.next_params.is_some(),
+            "results": page.results,
+        }))
+    }
+}
+
+async fn fetch_page(request: reqwest::RequestBuilder) -> anyhow::Result<SearchPage> {
+    let resp = request
+        .header(
+            "Accept",
+            "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+        )
+        .send()
+        .await
+        .map_err(|e| anyhow!("Request error: {}", e))?;
+    let body = resp
+        .text()
+        .await
+        .map_err(|e| anyhow!("Body error: {}", e))?;
+    Ok(parse_page(&body))
+}
+
+fn parse_page(body: &str) -> SearchPage {
+    let document = Html::parse_document(body);
+
+    let result_selector = Selector::parse(".web-result").unwrap();
+    let result_title_selector = Selector::parse(".result__a").unwrap();
+    let result_url_selector = Selector::parse(".result__url").unwrap();
+    let result_snippet_selector = Selector::parse(".result__snippet").unwrap();
+    let next_form_selector = Selector::parse(".nav-link form").unwrap();
+    let next_button_selector = Selector::parse("input[type=submit]").unwrap();
+    let hidden_input_selector = Selector::parse("input[type=hidden]").unwrap();
+
+    let results = document
+        .select(&result_selector)
+        .filter_map(|result| {
+            let title_node = result.select(&result_title_selector).next();
+            let title = title_node
+                .map(|n| n.text().collect::<Vec<_>>().join(""))
+                .unwrap_or_default();
+            let domain = result
+                .select(&result_url_selector)
+                .next()
+                .map(|n| n.text().collect::<Vec<_>>().join("").trim().to_string())
+                .unwrap_or_default();
+            let link = title_node
+                .and_then(|n| n.value().attr("href"))
+                .map(resolve_result_link)
+                .unwrap_or_else(|| domain.clone());
+            let snippet = result
+                .select(&result_snippet_selector)
+                .next()
+                .map(|n| n.text().collect::<Vec<_>>().join(""))
+                .unwrap_or_default();
+
+            if !title.is_empty() && !link.is_empty() {
+                Some(SearchResult {
+                    title,
+                    link,
+                    domain,
+                    snippet,
+                })
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // The first page only has a "Next" form; later pages also have a "Previous" one.
+    let next_params = document
+        .select(&next_form_selector)
+        .find(|form| {
+            form.select(&next_button_selector).any(|b| {
+                b.value()
+                    .attr("value")
+                    .is_some_and(|v| v.starts_with("Next"))
             })
-            .collect::<Vec<_>>();
+        })
+        .map(|form| {
+            form.select(&hidden_input_selector)
+                .filter_map(|input| {
+                    let name = input.value().attr("name")?;
+                    let value = input.value().attr("value").unwrap_or_default();
+                    Some((name.to_string(), value.to_string()))
+                })
+                .collect::<Vec<_>>()
+        });
 
-        Ok(serde_json::to_value(&results)?)
+    SearchPage {
+        results,
+        next_params,
     }
 }
+
+/// Turns DuckDuckGo's `//duckduckgo.com/l/?uddg=<target>` redirect into the target URL.
+fn resolve_result_link(href: &str) -> String {
+    let absolute = if href.starts_with("//") {
+        format!("https:{}", href)
+    } else {
+        href.to_string()
+    };
+    Url::parse(&absolute)
+        .ok()
+        .and_then(|url| {
+            url.query_pairs()
+                .find(|(k, _)| k == "uddg")
+                .map(|(_, v)| v.into_owned())
+        })
+        .unwrap_or(absolute)
+}
@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Error};
+use genai::chat::ChatMessage;
+use serde_json::{json, Value};
+use tracing::debug;
+
+use crate::llm::{AiService, LLMService};
+use crate::tools::website::fetch_markdown;
+use crate::tools::{AiTool, ToolContext, ToolError};
+
+const MAX_PAGE_CHARS: usize = 20_000; // Keeps long articles inside the summarizer's context window
+
+const SUMMARY_PROMPT: &str = "You summarize web pages. Reply with only the summary: plain text, \
+no headings, lists or preamble, at most two sentences and under 250 characters. If the page \
+has no real content (an error page, a login wall, a cookie banner), say so instead.";
+
+/// Tool that fetches a page and returns a short LLM-written summary of it.
+pub struct SummarizeTool {
+    summarizer: Arc<dyn AiService + Send + Sync>,
+}
+
+impl SummarizeTool {
+    /// Uses `summarizer` as-is; it should already be set up to produce short summaries.
+    pub fn new(summarizer: Arc<dyn AiService + Send + Sync>) -> Self {
+        Self { summarizer }
+    }
+
+    /// Builds a tool-less [`LLMService`] for `model` with a summarization prompt.
+    pub fn with_model(model: &str) -> Result<Self, Error> {
+        let summarizer = LLMService::new(Some(SUMMARY_PROMPT), vec![], model)?;
+        Ok(Self::new(Arc::new(summarizer)))
+    }
+}
+
+#[async_trait::async_trait]
+impl AiTool for SummarizeTool {
+    fn name(&self) -> &str {
+        "summarize_url"
+    }
+
+    fn description(&self) -> &str {
+        r#"Fetches a web page and returns a short summary of it. Prefer this over `website` when someone shares a link and asks what it's about.
+Parameters:
+- `url`: The URL of the page to summarize.
+- `focus`: Optional question or topic the summary should focus on.
+Example usage: { "url": "https://example.com/article", "focus": "what changed" }
+"#
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "url": { "type": "string", "description": "The page to summarize" },
+                "focus": { "type": "string", "description": "Question or topic to focus on" }
+            },
+            "required": ["url"]
+        })
+    }
+
+    async fn execute(&self, args: &Value, _ctx: &ToolContext<'_>) -> Result<Value, Error> {
+        let url = args
+            .get("url")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::InvalidArgs("Missing 'url' parameter".to_string()))?;
+        let focus = args
+            .get("focus")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|f| !f.is_empty());
+
+        let markdown = fetch_markdown(url).await?;
+        if markdown.trim().is_empty() {
+            return Err(anyhow!("The page at '{}' has no readable content", url));
+        }
+        let page: String = markdown.chars().take(MAX_PAGE_CHARS).collect();
+
+        let mut request = format!("Summarize this page from {}:\n\n{}", url, page);
+        if let Some(focus) = focus {
+            request.push_str(&format!("\n\nFocus the summary on: {}", focus));
+        }
+
+        let response = self
+            .summarizer
+            .generate_response(&[ChatMessage::user(request)], None)
+            .await?;
+        // Reasoning models put their thinking before the answer
+        let summary = response
+            .rsplit("</think>")
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        debug!("Summarized {} in {} chars", url, summary.len());
+
+        Ok(json!({
+            "url": url,
+            "summary": summary,
+        }))
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::{anyhow, Error};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::debug;
+
+use crate::tools::{http_client, AiTool, ToolContext, ToolError};
+
+const DEFAULT_LIBRETRANSLATE_URL: &str = "https://libretranslate.com";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TranslateResponse {
+    translated_text: String,
+    detected_language: Option<DetectedLanguage>,
+}
+
+#[derive(Deserialize)]
+struct DetectedLanguage {
+    language: String,
+    confidence: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+/// Tool that translates text through a LibreTranslate instance.
+pub struct TranslateTool {
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl TranslateTool {
+    pub fn new(base_url: &str, api_key: Option<String>) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+        }
+    }
+
+    /// Reads `LIBRETRANSLATE_URL` (defaults to the public instance) and `LIBRETRANSLATE_API_KEY`.
+    pub fn from_env() -> Self {
+        let base_url = std::env::var("LIBRETRANSLATE_URL")
+            .unwrap_or_else(|_| DEFAULT_LIBRETRANSLATE_URL.to_string());
+        let api_key = std::env::var("LIBRETRANSLATE_API_KEY")
+            .ok()
+            .filter(|k| !k.is_empty());
+        Self::new(&base_url, api_key)
+    }
+}
+
+#[async_trait::async_trait]
+impl AiTool for TranslateTool {
+    fn name(&self) -> &str {
+        "translate"
+    }
+
+    fn description(&self) -> &str {
+        r#"Translates text between languages.
+Parameters:
+- `text`: The text to translate.
+- `to`: The target language code, e.g. "en", "ja", "de".
+- `from`: The source language code, or "auto" to detect it (default is "auto").
+Returns the translated text and the detected source language.
+Example usage: { "text": "おはようございます", "to": "en", "from": "auto" }
+"#
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "text": { "type": "string", "description": "The text to translate" },
+                "to": { "type": "string", "description": "Target language code, e.g. \"en\"" },
+                "from": { "type": "string", "description": "Source language code", "default": "auto" }
+            },
+            "required": ["text", "to"]
+        })
+    }
+
+    async fn execute(&self, args: &Value, _ctx: &ToolContext<'_>) -> Result<Value, Error> {
+        let text = args
+            .get("text")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::InvalidArgs("Missing 'text' parameter".to_string()))?;
+        let to = args
+            .get("to")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::InvalidArgs("Missing 'to' parameter".to_string()))?;
+        let from = args.get("from").and_then(|v| v.as_str()).unwrap_or("auto");
+
+        let mut body = json!({
+            "q": text,
+            "source": from,
+            "target": to,
+            "format": "text",
+        });
+        if let Some(api_key) = &self.api_key {
+            body["api_key"] = json!(api_key);
+        }
+
+        let resp = http_client()
+            .post(format!("{}/translate", self.base_url))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| anyhow!("Request error: {}", e))?;
+
+        debug!("Response status: {}", resp.status());
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let message = resp
+                .json::<ErrorResponse>()
+                .await
+                .map(|e| e.error)
+                .unwrap_or_else(|_| status.to_string());
+            return Err(anyhow!("Translation failed: {}", message));
+        }
+
+        let translated: TranslateResponse = resp
+            .json()
+            .await
+            .map_err(|e| anyhow!("Body error: {}", e))?;
+
+        let (source, confidence) = match translated.detected_language {
+            Some(detected) => (detected.language, detected.confidence),
+            None => (from.to_string(), None),
+        };
+
+        Ok(json!({
+            "translated_text": translated.translated_text,
+            "source_language": source,
+            "confidence": confidence,
+        }))
+    }
+}
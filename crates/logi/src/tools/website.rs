@@ -1,11 +1,36 @@
 use anyhow::{anyhow, Error};
-use serde_json::Value;
+use serde_json::{json, Value};
 use tracing::debug;
 
-use crate::tools::AiTool;
+use crate::tools::{get_public, AiTool, ToolContext, ToolError};
 
-/// Example tool that performs basic math operations.
-pub struct WebsiteTool;
+const DEFAULT_MAX_CHARS: usize = 20_000; // Roughly 5k tokens, leaving room for the rest of the conversation
+const TRUNCATED_MARKER: &str = "[content truncated]";
+
+/// Tool that fetches a web page, rendered as Markdown by default.
+///
+/// Content longer than `max_chars` characters is cut at a word boundary, so one huge
+/// page can't take up the model's whole context.
+pub struct WebsiteTool {
+    max_chars: usize,
+}
+
+impl WebsiteTool {
+    pub fn new(max_chars: usize) -> Self {
+        Self { max_chars }
+    }
+
+    /// Reads `WEBSITE_MAX_CHARS`, the default for the `max_chars` parameter.
+    pub fn from_env() -> Self {
+        Self::new(
+            std::env::var("WEBSITE_MAX_CHARS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&max| max > 0)
+                .unwrap_or(DEFAULT_MAX_CHARS),
+        )
+    }
+}
 
 #[async_trait::async_trait]
 impl AiTool for WebsiteTool {
@@ -18,44 +43,213 @@ impl AiTool for WebsiteTool {
 Parameters:
 - `website`: The URL of the website to fetch.
 - `render`: Which format to render the content in. Options are "html" or "md" (default is "md").
+- `max_chars`: (Optional) Longest content to return; longer pages are cut short and marked "[content truncated]".
+Returns the content along with its full length in characters, and whether it was truncated.
 Example usage: { "website": "https://example.com", "render": "md"}
 "#
     }
 
-    async fn execute(&self, args: &Value) -> Result<Value, Error> {
-        let client = reqwest::Client::new();
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "website": { "type": "string", "description": "The URL to fetch" },
+                "render": { "type": "string", "enum": ["html", "md"], "default": "md" },
+                "max_chars": { "type": "integer", "minimum": 1, "default": self.max_chars }
+            },
+            "required": ["website"]
+        })
+    }
+
+    async fn execute(&self, args: &Value, _ctx: &ToolContext<'_>) -> Result<Value, Error> {
         let website = args
             .get("website")
             .and_then(|v| v.as_str())
-            .ok_or_else(|| anyhow!("Missing 'website' parameter"))?;
+            .ok_or_else(|| ToolError::InvalidArgs("Missing 'website' parameter".to_string()))?;
         let render = args.get("render").and_then(|v| v.as_str()).unwrap_or("md");
+        if render != "html" && render != "md" {
+            return Err(ToolError::InvalidArgs(
+                "Invalid 'render' parameter, must be 'html' or 'md'".to_string(),
+            )
+            .into());
+        }
+        let max_chars = match args.get("max_chars") {
+            None | Some(Value::Null) => self.max_chars,
+            Some(value) => value
+                .as_u64()
+                .filter(|&max| max > 0)
+                .map(|max| max as usize)
+                .ok_or_else(|| {
+                    ToolError::InvalidArgs("'max_chars' must be a positive integer".to_string())
+                })?,
+        };
+
+        let page = fetch_page(website).await?;
+        let Some(content) = page.render(render == "md") else {
+            return Ok(json!({
+                "content": format!(
+                    "Not a text document: {} ({} bytes), so its content can't be shown.",
+                    page.content_type,
+                    page.body.len()
+                ),
+                "content_type": page.content_type,
+            }));
+        };
+
+        let length = content.chars().count();
+        let (content, truncated) = match truncate_content(&content, max_chars) {
+            Some(cut) => {
+                debug!(
+                    "Truncated {} characters of content to {}",
+                    length, max_chars
+                );
+                (cut, true)
+            }
+            None => (content, false),
+        };
+        Ok(json!({
+            "content": content,
+            "content_type": page.content_type,
+            "length": length,
+            "truncated": truncated,
+        }))
+    }
+}
+
+/// Cuts `text` to at most `max_chars` characters at the last word boundary and marks
+/// it as truncated, or `None` if it already fits.
+fn truncate_content(text: &str, max_chars: usize) -> Option<String> {
+    if text.chars().count() <= max_chars {
+        return None;
+    }
+    let cut: String = text.chars().take(max_chars).collect();
+    let cut = match cut.rfind(char::is_whitespace) {
+        // don't throw away most of the budget for one long word
+        Some(space) if space > cut.len() / 2 => &cut[..space],
+        _ => cut.as_str(),
+    };
+    Some(format!("{}\n\n{}", cut.trim_end(), TRUNCATED_MARKER))
+}
+
+/// What a response body holds, going by its `Content-Type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ContentKind {
+    Html,
+    Json,
+    Text,
+    Binary,
+}
+
+impl ContentKind {
+    fn of(content_type: &str) -> Self {
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        match mime.as_str() {
+            // servers that send no type are nearly always serving HTML
+            "" | "text/html" | "application/xhtml+xml" => Self::Html,
+            "application/json" => Self::Json,
+            m if m.ends_with("+json") => Self::Json,
+            m if m.starts_with("text/") => Self::Text,
+            "application/xml" | "application/javascript" => Self::Text,
+            m if m.ends_with("+xml") => Self::Text,
+            _ => Self::Binary,
+        }
+    }
+}
+
+/// A fetched response body and its `Content-Type`.
+struct Page {
+    content_type: String,
+    body: Vec<u8>,
+}
+
+impl Page {
+    fn kind(&self) -> ContentKind {
+        ContentKind::of(&self.content_type)
+    }
+
+    /// Renders the body as text: HTML as Markdown when `markdown` is set or raw
+    /// otherwise, JSON pretty-printed and other text as-is. `None` for binary bodies.
+    fn render(&self, markdown: bool) -> Option<String> {
+        let text = String::from_utf8_lossy(&self.body);
+        match self.kind() {
+            ContentKind::Html if markdown => {
+                let markdown = html2md::rewrite_html(&text, false);
+                debug!("Converted HTML to Markdown, length: {}", markdown.len());
+                Some(markdown)
+            }
+            ContentKind::Json => Some(
+                serde_json::from_str::<Value>(&text)
+                    .ok()
+                    .and_then(|json| serde_json::to_string_pretty(&json).ok())
+                    .unwrap_or_else(|| text.into_owned()),
+            ),
+            ContentKind::Html | ContentKind::Text => Some(text.into_owned()),
+            ContentKind::Binary => None,
+        }
+    }
+}
+
+/// Fetches a public URL and returns its body along with its content type. Internal
+/// addresses are refused, see [`get_public`].
+async fn fetch_page(website: &str) -> Result<Page, Error> {
+    read_page(get_public(website).await?).await
+}
+
+/// Reads a fetched page's body along with its content type.
+async fn read_page(resp: reqwest::Response) -> Result<Page, Error> {
+    debug!("Response status: {}", resp.status());
+
+    let content_type = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let body = resp
+        .bytes()
+        .await
+        .map_err(|e| anyhow!("Body error: {}", e))?
+        .to_vec();
+
+    debug!("Response body length: {} ({})", body.len(), content_type);
+    Ok(Page { content_type, body })
+}
+
+/// Fetches a public page and converts it to Markdown, as the `website` tool renders it
+/// by default. Non-HTML text comes back as-is, and binary content is an error.
+pub(crate) async fn fetch_markdown(website: &str) -> Result<String, Error> {
+    let page = fetch_page(website).await?;
+    page.render(true).ok_or_else(|| {
+        anyhow!(
+            "{} is not a text document but {} ({} bytes)",
+            website,
+            page.content_type,
+            page.body.len()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let resp = client
-            .get(website)
-            .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/114.0.0.0 Safari/537.36 Edg/114.0.1823.67a")
-            .send()
-            .await
-            .map_err(|e| anyhow!("Request error: {}", e))?;
-
-        debug!("Response status: {}", resp.status());
-
-        let body = resp
-            .text()
-            .await
-            .map_err(|e| anyhow!("Body error: {}", e))?;
-
-        debug!("Response body length: {}", body.len());
-
-        if render == "html" {
-            Ok(serde_json::json!({ "content": body }))
-        } else if render == "md" {
-            let markdown = html2md::rewrite_html(&body, false);
-            debug!("Converted HTML to Markdown, length: {}", markdown.len());
-            Ok(serde_json::json!({ "content": markdown }))
-        } else {
-            Err(anyhow!(
-                "Invalid 'render' parameter, must be 'html' or 'md'"
-            ))
+    #[tokio::test]
+    async fn internal_pages_are_refused() {
+        let tool = WebsiteTool::new(DEFAULT_MAX_CHARS);
+        for url in [
+            "http://127.0.0.1:8080/",
+            "http://169.254.169.254/latest/meta-data/",
+        ] {
+            let err = tool
+                .execute(&json!({ "website": url }), &ToolContext::default())
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains("non-public address"), "{}", err);
         }
     }
 }
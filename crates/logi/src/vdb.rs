@@ -1,169 +1,552 @@
+pub use qdrant_client::qdrant::Distance;
 use qdrant_client::{
     config::QdrantConfig,
     qdrant::{
-        r#match::MatchValue, CreateCollectionBuilder, Distance, FieldCondition, Filter,
-        HnswConfigDiffBuilder, Match, PointStruct, ScoredPoint, SearchPointsBuilder,
-        UpsertPointsBuilder, Value, VectorParamsBuilder,
+        point_id::PointIdOptions, r#match::MatchValue, vectors_config, Condition,
+        CountPointsBuilder, CreateCollectionBuilder, DeletePointsBuilder, FieldCondition, Filter,
+        GetPointsBuilder, HnswConfigDiffBuilder, Match, PointId, PointStruct, QuantizationType,
+        ScalarQuantizationBuilder, ScoredPoint, ScrollPointsBuilder, SearchPointsBuilder,
+        UpsertPointsBuilder, Value, VectorParamsBuilder, VectorsConfigBuilder,
     },
-    Qdrant,
+    Qdrant, QdrantError,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use tracing::debug;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tonic::Code;
+use tracing::{debug, warn};
 
+/// A stored memory. Everything but `content` defaults when missing, so points
+/// written before a field existed still load.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MemoryEntry {
+    #[serde(default)]
     pub id: String,
     pub content: String,
+    #[serde(default)]
     pub embedding: Vec<f32>,
+    #[serde(default)]
     pub timestamp: i64, // unix timestamp in seconds
+    #[serde(default)]
     pub tags: Vec<String>,
-    pub role: String,       // "user" or "assistant"
+    #[serde(default)]
+    pub role: String, // "user" or "assistant"
+    #[serde(default)]
     pub entry_type: String, // e.g. "message", "fact", "tool_result"
+    #[serde(default)]
     pub conversation_id: String,
 }
 
+const DEFAULT_QDRANT_RETRIES: u32 = 3; // Extra attempts for a Qdrant call that failed transiently
+const QDRANT_RETRY_BASE_DELAY: Duration = Duration::from_millis(500); // Doubled after each retry
+const SCROLL_PAGE_SIZE: u32 = 256; // Points fetched per request when listing by filter
+
+/// Index and storage settings applied when the collection is first created.
+///
+/// The default keeps payload and HNSW index on disk and uses Qdrant's defaults for
+/// everything else.
+#[derive(Debug, Clone, Default)]
+pub struct CollectionTuning {
+    /// HNSW edges per node. Higher improves recall at the cost of memory.
+    pub hnsw_m: Option<u64>,
+    /// HNSW build-time neighbour count. Higher improves recall at the cost of indexing time.
+    pub hnsw_ef_construct: Option<u64>,
+    /// Store an int8 scalar-quantized copy of the vectors in RAM to cut memory use.
+    pub scalar_quantization: bool,
+}
+
+impl CollectionTuning {
+    /// Reads `QDRANT_HNSW_M`, `QDRANT_HNSW_EF_CONSTRUCT` and `QDRANT_QUANTIZATION` (`true`/`1`).
+    pub fn from_env() -> Self {
+        let parse = |name: &str| std::env::var(name).ok().and_then(|v| v.parse().ok());
+        Self {
+            hnsw_m: parse("QDRANT_HNSW_M"),
+            hnsw_ef_construct: parse("QDRANT_HNSW_EF_CONSTRUCT"),
+            scalar_quantization: std::env::var("QDRANT_QUANTIZATION")
+                .map(|v| v.eq_ignore_ascii_case("true") || v == "1")
+                .unwrap_or(false),
+        }
+    }
+}
+
+/// A vector stored under its own name on each point, e.g. a content embedding next
+/// to a topic embedding.
+#[derive(Debug, Clone)]
+pub struct NamedVector {
+    pub name: String,
+    pub dim: usize,
+    pub distance: Distance,
+}
+
+/// The vectors each point in a collection carries.
+#[derive(Debug, Clone)]
+pub enum VectorLayout {
+    /// One unnamed vector, filled from `MemoryEntry::embedding`.
+    Single { dim: usize, distance: Distance },
+    /// Several named vectors, written with [`MemoryStore::put_vectors`] and searched
+    /// by name.
+    Named(Vec<NamedVector>),
+}
+
+/// An embedding that doesn't match the size of the collection's vectors, caught before
+/// Qdrant rejects it with a less obvious error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DimensionMismatch {
+    pub embedding: usize,
+    pub collection: usize,
+}
+
+impl std::fmt::Display for DimensionMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "embedding dim {} != collection dim {}, check EMBEDDING_DIM against the embedding model",
+            self.embedding, self.collection
+        )
+    }
+}
+
+impl std::error::Error for DimensionMismatch {}
+
 pub struct MemoryStore {
     client: Qdrant,
     collection_name: String,
+    retries: u32,
+    tuning: CollectionTuning,
+    /// Size of the unnamed vector, or 0 when unknown or the collection uses named vectors.
+    embedding_dim: AtomicUsize,
 }
 
 impl MemoryStore {
+    /// Connects to Qdrant at `url`, creating the collection with a single unnamed
+    /// vector of `embedding_dim` if needed. `QDRANT_API_KEY` is sent when set, and
+    /// `QDRANT_RETRIES` bounds retries of transient failures.
     pub async fn new(
         url: &str,
         collection_name: &str,
         embedding_dim: usize,
+        tuning: CollectionTuning,
+    ) -> anyhow::Result<Self> {
+        let layout = VectorLayout::Single {
+            dim: embedding_dim,
+            distance: Distance::Cosine,
+        };
+        Self::with_layout(url, collection_name, layout, tuning).await
+    }
+
+    /// Like [`MemoryStore::new`], but creates a missing collection with the given vector
+    /// layout, e.g. several named vectors per point. An existing collection is used as is.
+    pub async fn with_layout(
+        url: &str,
+        collection_name: &str,
+        layout: VectorLayout,
+        tuning: CollectionTuning,
     ) -> anyhow::Result<Self> {
-        let config = QdrantConfig::from_url(url);
-        let client = Qdrant::new(config)?;
+        let url = normalize_qdrant_url(url)?;
+        let url = url.as_str();
+
+        // https URLs get TLS from the client itself; an API key is needed for hosted Qdrant
+        let mut config = QdrantConfig::from_url(url);
+        if let Some(api_key) = std::env::var("QDRANT_API_KEY")
+            .ok()
+            .filter(|k| !k.is_empty())
+        {
+            if !url.starts_with("https://") {
+                warn!(
+                    "QDRANT_API_KEY is set but QDRANT_URL isn't https, the key is sent unencrypted"
+                );
+            }
+            config.set_api_key(&api_key);
+        }
+        let client = Qdrant::new(config)
+            .map_err(|e| anyhow::anyhow!("could not set up Qdrant client for {}: {}", url, e))?;
+
+        // Also the first request, so this is where an unreachable Qdrant shows up
+        let exists = client
+            .collection_exists(collection_name)
+            .await
+            .map_err(|e| {
+                let hint = if url.ends_with(":6333") {
+                    " (6333 is Qdrant's REST port, this client needs the gRPC port, usually 6334)"
+                } else {
+                    ""
+                };
+                anyhow::anyhow!("could not reach Qdrant at {}: {}{}", url, e, hint)
+            })?;
 
         // Create collection if it doesn't exist
-        if let Err(_) = client.collection_info(collection_name).await {
-            client
-                .create_collection(
-                    CreateCollectionBuilder::new(collection_name)
-                        .on_disk_payload(true)
-                        .hnsw_config(HnswConfigDiffBuilder::default().on_disk(true))
-                        .vectors_config(VectorParamsBuilder::new(
-                            embedding_dim as u64,
-                            Distance::Cosine,
-                        )),
-                )
-                .await?;
+        if !exists {
+            create_collection(&client, collection_name, &layout, &tuning).await?;
         }
 
+        let retries = std::env::var("QDRANT_RETRIES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_QDRANT_RETRIES);
+
+        // an existing collection may have been created with a different size
+        let embedding_dim = collection_vector_dim(&client, collection_name)
+            .await?
+            .unwrap_or(0);
+
         Ok(Self {
             client,
             collection_name: collection_name.to_string(),
+            retries,
+            tuning,
+            embedding_dim: AtomicUsize::new(embedding_dim),
         })
     }
 
-    pub async fn put(&self, entry: MemoryEntry) -> anyhow::Result<()> {
-        let mut payload_map: HashMap<String, Value> = HashMap::new();
-        payload_map.insert("content".to_string(), Value::from(entry.content.clone()));
-        payload_map.insert("timestamp".to_string(), Value::from(entry.timestamp));
-        payload_map.insert("tags".to_string(), Value::from(entry.tags.clone()));
-        payload_map.insert("role".to_string(), Value::from(entry.role.clone()));
-        payload_map.insert(
-            "entry_type".to_string(),
-            Value::from(entry.entry_type.clone()),
+    /// A store that never connects, so every call on it fails. For tests that need
+    /// memory enabled but expect nothing to reach Qdrant.
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn unconnected(collection_name: &str) -> anyhow::Result<Self> {
+        let config = QdrantConfig::from_url("http://127.0.0.1:1").skip_compatibility_check();
+        Ok(Self {
+            client: Qdrant::new(config)?,
+            collection_name: collection_name.to_string(),
+            retries: 0,
+            tuning: CollectionTuning::default(),
+            embedding_dim: AtomicUsize::new(0),
+        })
+    }
+
+    /// Deletes the collection and every memory in it, then creates it again empty.
+    ///
+    /// Used to migrate to a different embedding dimension or distance. This cannot be
+    /// undone, so callers should get explicit confirmation first.
+    pub async fn recreate(&self, embedding_dim: usize, distance: Distance) -> anyhow::Result<()> {
+        warn!(
+            "DELETING Qdrant collection '{}' and all stored memories",
+            self.collection_name
         );
-        payload_map.insert(
-            "conversation_id".to_string(),
-            Value::from(entry.conversation_id.clone()),
+        if self.client.collection_exists(&self.collection_name).await? {
+            self.client.delete_collection(&self.collection_name).await?;
+        }
+        let layout = VectorLayout::Single {
+            dim: embedding_dim,
+            distance,
+        };
+        create_collection(&self.client, &self.collection_name, &layout, &self.tuning).await?;
+        self.embedding_dim.store(embedding_dim, Ordering::Relaxed);
+        warn!(
+            "Recreated Qdrant collection '{}' ({} dimensions, {:?})",
+            self.collection_name, embedding_dim, distance
         );
+        Ok(())
+    }
+
+    /// Returns the size of the collection's unnamed vector, or `None` if it uses named
+    /// vectors.
+    pub async fn vector_dim(&self) -> anyhow::Result<Option<usize>> {
+        collection_vector_dim(&self.client, &self.collection_name).await
+    }
 
-        self.client
-            .upsert_points(UpsertPointsBuilder::new(
-                &self.collection_name,
-                vec![PointStruct::new(entry.id, entry.embedding, payload_map)],
-            ))
+    /// Fails with [`DimensionMismatch`] if `embedding` can't go in the unnamed vector.
+    fn check_dim(&self, embedding: &[f32]) -> anyhow::Result<()> {
+        let collection = self.embedding_dim.load(Ordering::Relaxed);
+        if collection != 0 && embedding.len() != collection {
+            return Err(DimensionMismatch {
+                embedding: embedding.len(),
+                collection,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Returns the name of the Qdrant collection backing this store.
+    pub fn collection_name(&self) -> &str {
+        &self.collection_name
+    }
+
+    /// Sets how many times a transiently failing Qdrant call is retried.
+    /// Defaults to `QDRANT_RETRIES`, or 3 if unset.
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Runs a Qdrant call, retrying with backoff while it fails with a transient error.
+    async fn retry<T, F, Fut>(&self, operation: &str, mut call: F) -> Result<T, QdrantError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, QdrantError>>,
+    {
+        let mut delay = QDRANT_RETRY_BASE_DELAY;
+        let mut attempt = 0;
+        loop {
+            match call().await {
+                Err(e) if attempt < self.retries && is_transient(&e) => {
+                    attempt += 1;
+                    warn!(
+                        "Qdrant {} failed ({}/{}): {}, retrying in {:?}",
+                        operation, attempt, self.retries, e, delay
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    pub async fn put(&self, entry: MemoryEntry) -> anyhow::Result<()> {
+        self.check_dim(&entry.embedding)?;
+        let payload_map = entry_payload(&entry);
+        let point = PointStruct::new(entry.id, entry.embedding, payload_map);
+        self.upsert(point).await
+    }
+
+    /// Stores `entry` with one vector per name, for collections created with
+    /// [`VectorLayout::Named`]. `entry.embedding` is ignored.
+    pub async fn put_vectors(
+        &self,
+        entry: MemoryEntry,
+        vectors: HashMap<String, Vec<f32>>,
+    ) -> anyhow::Result<()> {
+        let payload_map = entry_payload(&entry);
+        let point = PointStruct::new(entry.id, vectors, payload_map);
+        self.upsert(point).await
+    }
+
+    async fn upsert(&self, point: PointStruct) -> anyhow::Result<()> {
+        let request = UpsertPointsBuilder::new(&self.collection_name, vec![point]).build();
+        self.retry("upsert", || self.client.upsert_points(request.clone()))
             .await?;
 
         Ok(())
     }
 
     pub async fn put_batch(&self, entries: Vec<MemoryEntry>) -> anyhow::Result<()> {
+        for entry in &entries {
+            self.check_dim(&entry.embedding)?;
+        }
         let points: Vec<PointStruct> = entries
             .into_iter()
             .map(|entry| {
-                let mut payload_map: HashMap<String, Value> = HashMap::new();
-                payload_map.insert("content".to_string(), Value::from(entry.content.clone()));
-                payload_map.insert("timestamp".to_string(), Value::from(entry.timestamp));
-                payload_map.insert("tags".to_string(), Value::from(entry.tags.clone()));
-                payload_map.insert("role".to_string(), Value::from(entry.role.clone()));
-                payload_map.insert(
-                    "entry_type".to_string(),
-                    Value::from(entry.entry_type.clone()),
-                );
-                payload_map.insert(
-                    "conversation_id".to_string(),
-                    Value::from(entry.conversation_id.clone()),
-                );
+                let payload_map = entry_payload(&entry);
                 PointStruct::new(entry.id, entry.embedding, payload_map)
             })
             .collect();
 
-        self.client
-            .upsert_points(UpsertPointsBuilder::new(&self.collection_name, points))
+        let request = UpsertPointsBuilder::new(&self.collection_name, points).build();
+        self.retry("upsert", || self.client.upsert_points(request.clone()))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Fetches the entry stored under `id`, if any.
+    pub async fn get(&self, id: &str) -> anyhow::Result<Option<MemoryEntry>> {
+        let request = GetPointsBuilder::new(&self.collection_name, vec![PointId::from(id)])
+            .with_payload(true)
+            .with_vectors(false)
+            .build();
+        let response = self
+            .retry("get", || self.client.get_points(request.clone()))
             .await?;
+        let Some(point) = response.result.into_iter().next() else {
+            return Ok(None);
+        };
+        Ok(Some(entry_from_point(point.id, point.payload)?))
+    }
 
+    /// Deletes the entry stored under `id`. Deleting a missing entry is not an error.
+    pub async fn delete(&self, id: &str) -> anyhow::Result<()> {
+        let request = DeletePointsBuilder::new(&self.collection_name)
+            .points(vec![PointId::from(id)])
+            .build();
+        self.retry("delete", || self.client.delete_points(request.clone()))
+            .await?;
         Ok(())
     }
 
+    /// Counts the entries carrying all of `tags`.
+    pub async fn count_tagged(&self, tags: Vec<String>) -> anyhow::Result<u64> {
+        let filter = Filter {
+            must: tags
+                .into_iter()
+                .map(|tag| keyword_condition("tags", tag))
+                .collect(),
+            ..Default::default()
+        };
+        let request = CountPointsBuilder::new(&self.collection_name)
+            .filter(filter)
+            .exact(true)
+            .build();
+        let response = self
+            .retry("count", || self.client.count(request.clone()))
+            .await?;
+        Ok(response.result.map(|r| r.count).unwrap_or(0))
+    }
+
+    /// Whether a point with `id` is already stored.
+    pub async fn exists(&self, id: &str) -> anyhow::Result<bool> {
+        Ok(!self.existing_ids(&[id.to_string()]).await?.is_empty())
+    }
+
+    /// Returns which of `ids` are already stored, in one request and without fetching
+    /// payloads or vectors.
+    pub async fn existing_ids(&self, ids: &[String]) -> anyhow::Result<HashSet<String>> {
+        if ids.is_empty() {
+            return Ok(HashSet::new());
+        }
+        let point_ids: Vec<PointId> = ids.iter().map(|id| PointId::from(id.as_str())).collect();
+        let request = GetPointsBuilder::new(&self.collection_name, point_ids)
+            .with_payload(false)
+            .with_vectors(false)
+            .build();
+        let response = self
+            .retry("get", || self.client.get_points(request.clone()))
+            .await?;
+
+        Ok(response
+            .result
+            .into_iter()
+            .filter_map(|point| point_id_string(point.id))
+            .collect())
+    }
+
+    /// Like [`MemoryStore::put_batch`], but skips entries whose ID is already stored,
+    /// saving the upsert when the same posts are processed again. Returns how many
+    /// entries were skipped.
+    pub async fn put_batch_if_absent(&self, entries: Vec<MemoryEntry>) -> anyhow::Result<usize> {
+        let ids: Vec<String> = entries.iter().map(|e| e.id.clone()).collect();
+        let existing = self.existing_ids(&ids).await?;
+        let total = entries.len();
+        let missing: Vec<MemoryEntry> = entries
+            .into_iter()
+            .filter(|e| !existing.contains(&e.id))
+            .collect();
+
+        let skipped = total - missing.len();
+        if skipped > 0 {
+            debug!("skipping {} memory writes that already exist", skipped);
+            crate::MEMORY_WRITES_SKIPPED.increment(skipped as u64);
+        }
+        if !missing.is_empty() {
+            self.put_batch(missing).await?;
+        }
+        Ok(skipped)
+    }
+
+    /// Returns up to `top_k` entries most similar to `embedding`, optionally restricted
+    /// to entries carrying all of `tags` and scoring at least `min_score`.
+    ///
+    /// `vector_name` picks which named vector to compare against; `None` searches the
+    /// unnamed default vector.
     pub async fn get_similar(
         &self,
+        vector_name: Option<&str>,
         embedding: Vec<f32>,
         tags: Option<Vec<String>>,
         top_k: usize,
+        min_score: Option<f32>,
     ) -> anyhow::Result<Vec<MemoryEntry>> {
         // Build filter for tags if provided
         let filter = tags.map(|tags| Filter {
             must: tags
                 .into_iter()
-                .map(|tag| {
-                    FieldCondition {
-                        key: "tags".to_string(),
-                        r#match: Some(MatchValue::Keyword(tag).into()),
-                        ..Default::default()
-                    }
-                    .into()
-                })
+                .map(|tag| keyword_condition("tags", tag))
                 .collect(),
             ..Default::default()
         });
 
+        self.search(vector_name, embedding, filter, top_k, min_score)
+            .await
+    }
+
+    /// Like [`MemoryStore::get_similar`], but matches entries carrying any of `tags`
+    /// rather than all of them.
+    pub async fn get_similar_any(
+        &self,
+        vector_name: Option<&str>,
+        embedding: Vec<f32>,
+        tags: Vec<String>,
+        top_k: usize,
+        min_score: Option<f32>,
+    ) -> anyhow::Result<Vec<MemoryEntry>> {
+        let filter = Filter {
+            should: tags
+                .into_iter()
+                .map(|tag| keyword_condition("tags", tag))
+                .collect(),
+            ..Default::default()
+        };
+
+        self.search(vector_name, embedding, Some(filter), top_k, min_score)
+            .await
+    }
+
+    /// Runs a similarity search with an optional payload filter.
+    async fn search(
+        &self,
+        vector_name: Option<&str>,
+        embedding: Vec<f32>,
+        filter: Option<Filter>,
+        top_k: usize,
+        min_score: Option<f32>,
+    ) -> anyhow::Result<Vec<MemoryEntry>> {
+        if vector_name.is_none() {
+            self.check_dim(&embedding)?;
+        }
         let mut builder = SearchPointsBuilder::new(&self.collection_name, embedding, top_k as u64)
             .with_payload(true);
 
+        if let Some(name) = vector_name {
+            builder = builder.vector_name(name);
+        }
         if let Some(f) = filter {
             builder = builder.filter(f);
         }
+        if let Some(min_score) = min_score {
+            builder = builder.score_threshold(min_score);
+        }
 
-        let search_result = self.client.search_points(builder).await?;
+        let request = builder.build();
+        let search_result = self
+            .retry("search", || self.client.search_points(request.clone()))
+            .await?;
         debug!("Search result: {:?}", &search_result.result);
 
-        // Deserialize results into MemoryEntry
-        let mut entries = Vec::new();
-        for point in search_result.result {
-            let entry: MemoryEntry = serde_json::from_value(serde_json::to_value(point.payload)?)?;
-            entries.push(entry);
-        }
-
-        Ok(entries)
+        // Deserialize results into MemoryEntry, dropping points that don't fit
+        Ok(search_result
+            .result
+            .into_iter()
+            .filter_map(|point| entry_or_warn(point.id, point.payload))
+            .collect())
     }
 
+    /// Returns every entry matching `filter`, scrolling through as many pages as needed.
     pub async fn get_by_filter(&self, filter: Filter) -> anyhow::Result<Vec<MemoryEntry>> {
-        // Use a dummy vector and large top_k to fetch by filter only
-        let builder = SearchPointsBuilder::new(&self.collection_name, vec![0.0; 1], 100)
-            .with_payload(true)
-            .filter(filter);
-
-        let search_result = self.client.search_points(builder).await?;
         let mut entries = Vec::new();
-        for point in search_result.result {
-            let entry: MemoryEntry = serde_json::from_value(serde_json::to_value(point.payload)?)?;
-            entries.push(entry);
+        let mut offset: Option<PointId> = None;
+        loop {
+            let mut builder = ScrollPointsBuilder::new(&self.collection_name)
+                .filter(filter.clone())
+                .limit(SCROLL_PAGE_SIZE)
+                .with_payload(true)
+                .with_vectors(false);
+            if let Some(offset) = offset.take() {
+                builder = builder.offset(offset);
+            }
+            let request = builder.build();
+            let page = self
+                .retry("scroll", || self.client.scroll(request.clone()))
+                .await?;
+            entries.extend(
+                page.result
+                    .into_iter()
+                    .filter_map(|point| entry_or_warn(point.id, point.payload)),
+            );
+            match page.next_page_offset {
+                Some(next) => offset = Some(next),
+                None => break,
+            }
         }
         Ok(entries)
     }
@@ -173,8 +556,12 @@ impl MemoryStore {
         query_embedding: Vec<f32>,
     ) -> anyhow::Result<Option<(MemoryEntry, Option<MemoryEntry>)>> {
         // Step 1: Find most similar user message
+        let user_filter = Filter {
+            must: vec![keyword_condition("role", "user")],
+            ..Default::default()
+        };
         let user_msgs = self
-            .get_similar(query_embedding, Some(vec!["user".to_string()]), 1)
+            .search(None, query_embedding, Some(user_filter), 1, None)
             .await?;
         let user_msg = match user_msgs.into_iter().next() {
             Some(m) => m,
@@ -184,18 +571,8 @@ impl MemoryStore {
         // Step 2: Find the next assistant message in the same conversation
         let filter = Filter {
             must: vec![
-                FieldCondition {
-                    key: "conversation_id".to_string(),
-                    r#match: Some(MatchValue::Keyword(user_msg.conversation_id.clone()).into()),
-                    ..Default::default()
-                }
-                .into(),
-                FieldCondition {
-                    key: "role".to_string(),
-                    r#match: Some(MatchValue::Keyword("assistant".to_string()).into()),
-                    ..Default::default()
-                }
-                .into(),
+                keyword_condition("conversation_id", user_msg.conversation_id.clone()),
+                keyword_condition("role", "assistant"),
             ],
             ..Default::default()
         };
@@ -212,16 +589,201 @@ impl MemoryStore {
 
     pub async fn get_chain(&self, conversation_id: &str) -> anyhow::Result<Vec<MemoryEntry>> {
         let filter = Filter {
-            must: vec![FieldCondition {
-                key: "conversation_id".to_string(),
-                r#match: Some(MatchValue::Keyword(conversation_id.to_string()).into()),
-                ..Default::default()
-            }
-            .into()],
+            must: vec![keyword_condition("conversation_id", conversation_id)],
             ..Default::default()
         };
         let mut convo_points = self.get_by_filter(filter).await?;
         convo_points.sort_by_key(|m| m.timestamp);
         Ok(convo_points)
     }
+
+    /// Renders every entry stored for `conversation_id` as a readable transcript,
+    /// oldest first. `None` if nothing is stored for it.
+    pub async fn transcript(&self, conversation_id: &str) -> anyhow::Result<Option<String>> {
+        let chain = self.get_chain(conversation_id).await?;
+        if chain.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(format_transcript(conversation_id, &chain)))
+    }
+}
+
+/// Formats `entries` as a transcript: a header, then each entry's time, role and type
+/// above its content.
+fn format_transcript(conversation_id: &str, entries: &[MemoryEntry]) -> String {
+    let mut transcript = format!(
+        "Conversation {} ({} entries)\n\n",
+        conversation_id,
+        entries.len()
+    );
+    for entry in entries {
+        let at = time::OffsetDateTime::from_unix_timestamp(entry.timestamp)
+            .ok()
+            .and_then(|at| {
+                at.format(&time::format_description::well_known::Rfc3339)
+                    .ok()
+            })
+            .unwrap_or_else(|| entry.timestamp.to_string());
+        transcript.push_str(&format!(
+            "[{}] {} ({})\n{}\n\n",
+            at,
+            entry.role,
+            entry.entry_type,
+            entry.content.trim()
+        ));
+    }
+    transcript
+}
+
+/// Builds a [`MemoryEntry`] from a stored point. The ID and vector aren't part of the
+/// payload, so the ID is taken from the point and the embedding left empty.
+fn entry_from_point(
+    id: Option<PointId>,
+    payload: HashMap<String, Value>,
+) -> anyhow::Result<MemoryEntry> {
+    let mut entry: MemoryEntry = serde_json::from_value(serde_json::to_value(payload)?)?;
+    entry.id = point_id_string(id).unwrap_or_default();
+    Ok(entry)
+}
+
+/// The string form of a point ID, as entry IDs are given.
+fn point_id_string(id: Option<PointId>) -> Option<String> {
+    match id?.point_id_options? {
+        PointIdOptions::Uuid(id) => Some(id),
+        PointIdOptions::Num(id) => Some(id.to_string()),
+    }
+}
+
+/// Like [`entry_from_point`], but logs and drops a point that can't be read, so one
+/// malformed or legacy point doesn't fail a whole batch.
+fn entry_or_warn(id: Option<PointId>, payload: HashMap<String, Value>) -> Option<MemoryEntry> {
+    let point_id = point_id_string(id.clone()).unwrap_or_default();
+    entry_from_point(id, payload)
+        .inspect_err(|e| warn!(point = point_id, error = %e, "Skipping unreadable memory point"))
+        .ok()
+}
+
+/// Reads the size of a collection's unnamed vector, `None` if it uses named vectors.
+async fn collection_vector_dim(
+    client: &Qdrant,
+    collection_name: &str,
+) -> anyhow::Result<Option<usize>> {
+    let info = client.collection_info(collection_name).await?;
+    let config = info
+        .result
+        .and_then(|info| info.config)
+        .and_then(|config| config.params)
+        .and_then(|params| params.vectors_config)
+        .and_then(|vectors| vectors.config);
+    Ok(match config {
+        Some(vectors_config::Config::Params(params)) => Some(params.size as usize),
+        _ => None,
+    })
+}
+
+/// Builds the Qdrant payload stored for `entry`.
+fn entry_payload(entry: &MemoryEntry) -> HashMap<String, Value> {
+    let mut payload_map: HashMap<String, Value> = HashMap::new();
+    payload_map.insert("content".to_string(), Value::from(entry.content.clone()));
+    payload_map.insert("timestamp".to_string(), Value::from(entry.timestamp));
+    payload_map.insert("tags".to_string(), Value::from(entry.tags.clone()));
+    payload_map.insert("role".to_string(), Value::from(entry.role.clone()));
+    payload_map.insert(
+        "entry_type".to_string(),
+        Value::from(entry.entry_type.clone()),
+    );
+    payload_map.insert(
+        "conversation_id".to_string(),
+        Value::from(entry.conversation_id.clone()),
+    );
+    payload_map
+}
+
+/// Builds a filter condition matching `key` exactly against `value`.
+fn keyword_condition(key: &str, value: impl Into<String>) -> Condition {
+    FieldCondition {
+        key: key.to_string(),
+        r#match: Some(MatchValue::Keyword(value.into()).into()),
+        ..Default::default()
+    }
+    .into()
+}
+
+/// Whether a Qdrant error is worth retrying, e.g. the server restarting, as opposed to
+/// a bad request or schema mismatch that will fail the same way again.
+fn is_transient(e: &QdrantError) -> bool {
+    match e {
+        QdrantError::ResponseError { status } => matches!(
+            status.code(),
+            Code::Unavailable | Code::DeadlineExceeded | Code::Aborted | Code::Cancelled
+        ),
+        QdrantError::ResourceExhaustedError { .. } | QdrantError::Io(_) => true,
+        _ => false,
+    }
+}
+
+async fn create_collection(
+    client: &Qdrant,
+    collection_name: &str,
+    layout: &VectorLayout,
+    tuning: &CollectionTuning,
+) -> anyhow::Result<()> {
+    let mut vectors = VectorsConfigBuilder::default();
+    match layout {
+        VectorLayout::Single { dim, distance } => {
+            vectors.add_vector_params(VectorParamsBuilder::new(*dim as u64, *distance));
+        }
+        VectorLayout::Named(named) => {
+            for v in named {
+                vectors.add_named_vector_params(
+                    v.name.clone(),
+                    VectorParamsBuilder::new(v.dim as u64, v.distance),
+                );
+            }
+        }
+    }
+
+    let mut hnsw = HnswConfigDiffBuilder::default().on_disk(true);
+    if let Some(m) = tuning.hnsw_m {
+        hnsw = hnsw.m(m);
+    }
+    if let Some(ef_construct) = tuning.hnsw_ef_construct {
+        hnsw = hnsw.ef_construct(ef_construct);
+    }
+
+    let mut collection = CreateCollectionBuilder::new(collection_name)
+        .on_disk_payload(true)
+        .hnsw_config(hnsw)
+        .vectors_config(vectors);
+    if tuning.scalar_quantization {
+        collection = collection.quantization_config(
+            ScalarQuantizationBuilder::default()
+                .r#type(QuantizationType::Int8.into())
+                .always_ram(true),
+        );
+    }
+
+    client.create_collection(collection).await?;
+    Ok(())
+}
+
+/// Checks that `url` is an absolute http(s) URL with a host, trimming whitespace and
+/// trailing slashes, so a typo fails at startup with a clear message.
+fn normalize_qdrant_url(url: &str) -> anyhow::Result<String> {
+    let trimmed = url.trim().trim_end_matches('/');
+    let parsed = reqwest::Url::parse(trimmed)
+        .map_err(|e| anyhow::anyhow!("invalid QDRANT_URL '{}': {}", url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(anyhow::anyhow!(
+            "invalid QDRANT_URL '{}': expected an http:// or https:// URL",
+            url
+        ));
+    }
+    if parsed.host_str().is_none_or(str::is_empty) {
+        return Err(anyhow::anyhow!(
+            "invalid QDRANT_URL '{}': missing host",
+            url
+        ));
+    }
+    Ok(trimmed.to_string())
 }